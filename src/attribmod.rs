@@ -0,0 +1,141 @@
+//! Powers with AttribMod effects, which change an attribute of a power (its recharge time, say).
+//! Mids keeps the original and modified values of the attribute on the effect, as atr_orig_* and
+//! atr_mod_*.
+
+use crate::{
+    cod,
+    mids::{self, enums::PowerAttribs},
+    tolerance::{Match, Tolerance, Tolerances},
+};
+
+/// The CoD attrib names that modify each power attribute, compared without case or underscores.
+fn cod_attribs(a: PowerAttribs) -> &'static [&'static str] {
+    match a {
+        PowerAttribs::None => &[],
+        PowerAttribs::Accuracy => &["accuracy"],
+        PowerAttribs::ActivateInterval => &["activateperiod", "activateinterval"],
+        PowerAttribs::Arc => &["arc"],
+        PowerAttribs::CastTime => &["casttime", "activationtime"],
+        PowerAttribs::EffectArea => &["effectarea"],
+        PowerAttribs::EnduranceCost => &["endurancecost"],
+        PowerAttribs::InterruptTime => &["interrupttime"],
+        PowerAttribs::MaxTargets => &["maxtargets", "maxtargetshit"],
+        PowerAttribs::Radius => &["radius"],
+        PowerAttribs::Range => &["range"],
+        PowerAttribs::RechargeTime => &["rechargetime"],
+        PowerAttribs::SecondaryRange => &["secondaryrange"],
+    }
+}
+
+fn is_attrib(cod_name: &str, a: PowerAttribs) -> bool {
+    let n: String = cod_name.chars().filter(|&c| c != '_').collect::<String>().to_ascii_lowercase();
+    cod_attribs(a).contains(&n.as_str())
+}
+
+fn tolerance(tolerances: &Tolerances, a: PowerAttribs) -> Tolerance {
+    match a {
+        PowerAttribs::Accuracy => tolerances.accuracy,
+        PowerAttribs::CastTime => tolerances.cast_time,
+        PowerAttribs::RechargeTime => tolerances.recharge_time,
+        _ => Tolerance::default(),
+    }
+}
+
+fn is_strength(aspect: &str) -> bool {
+    aspect.eq_ignore_ascii_case("str") || aspect.eq_ignore_ascii_case("strength")
+}
+
+/// The original and modified values of an AttribMod effect, where they're plain numbers.
+fn values(e: &mids::Effect) -> Option<(f32, f32)> {
+    Some(match e.power_attribs {
+        PowerAttribs::Accuracy => (e.atr_orig_accuracy, e.atr_mod_accuracy),
+        PowerAttribs::ActivateInterval => (e.atr_orig_activate_period, e.atr_mod_activate_period),
+        PowerAttribs::Arc => (e.atr_orig_arc as f32, e.atr_mod_arc as f32),
+        PowerAttribs::CastTime => (e.atr_orig_cast_time, e.atr_mod_cast_time),
+        PowerAttribs::EnduranceCost => (e.atr_orig_endurance_cost, e.atr_mod_endurance_cost),
+        PowerAttribs::InterruptTime => (e.atr_orig_interrupt_time, e.atr_mod_interrupt_time),
+        PowerAttribs::MaxTargets => (e.atr_orig_max_targets as f32, e.atr_mod_max_targets as f32),
+        PowerAttribs::Radius => (e.atr_orig_radius, e.atr_mod_radius),
+        PowerAttribs::Range => (e.atr_orig_range, e.atr_mod_range),
+        PowerAttribs::RechargeTime => (e.atr_orig_recharge_time, e.atr_mod_recharge_time),
+        PowerAttribs::SecondaryRange => (e.atr_orig_secondary_range, e.atr_mod_secondary_range),
+        PowerAttribs::None | PowerAttribs::EffectArea => return None,
+    })
+}
+
+/// Reconciles the AttribMod effects of `p` with the CoD power. The original timing is fixed from
+/// the CoD base values, and the modified values are checked against the CoD templates that modify
+/// the same attribute. Returns a description of each fix and each disagreement, and whether anything
+/// was changed. Values within tolerance are added to `near_misses` instead.
+pub fn sync(
+    p: &mut mids::Power,
+    cod_p: &cod::Power,
+    sync_recharge: bool,
+    sync_cast: bool,
+    tolerances: &Tolerances,
+    near_misses: &mut Vec<String>,
+) -> (Vec<String>, bool) {
+    let mut report = Vec::new();
+    let mut changed = false;
+    let templates = cod_p.templates();
+    for e in &mut p.effects {
+        let attrib = e.power_attribs;
+        let Some((_, modified)) = values(e) else {
+            continue;
+        };
+
+        // The original values are the unmodified power's, which CoD has for timing.
+        let base: Option<(&mut f32, f32, &Tolerance)> = match attrib {
+            PowerAttribs::RechargeTime if sync_recharge => Some((&mut e.atr_orig_recharge_time, cod_p.recharge_time, &tolerances.recharge_time)),
+            PowerAttribs::CastTime if sync_cast => Some((&mut e.atr_orig_cast_time, cod_p.activation_time, &tolerances.cast_time)),
+            PowerAttribs::Accuracy => Some((&mut e.atr_orig_accuracy, cod_p.accuracy, &tolerances.accuracy)),
+            _ => None,
+        };
+        if let Some((orig, cod_value, tol)) = base {
+            match tol.compare(*orig, cod_value) {
+                Match::Exact => {}
+                Match::Near => near_misses.push(format!(
+                    "{} ({}): effect {} atr_orig {:?} {} vs CoD {}",
+                    p.full_name, p.display_name, e.unique_id, attrib, orig, cod_value
+                )),
+                Match::Differs => {
+                    let expected = tol.expected(cod_value);
+                    report.push(format!(
+                        "{} ({}): effect {} atr_orig {:?} {} fixed to {}",
+                        p.full_name, p.display_name, e.unique_id, attrib, orig, expected
+                    ));
+                    *orig = expected;
+                    changed = true;
+                }
+            }
+        }
+
+        // atr_mod_* is the attribute's new value, which corresponds to a Cur template; Str templates
+        // scale the attribute instead and can't be compared with it.
+        let (cur, strength): (Vec<&&cod::Template>, Vec<&&cod::Template>) = templates
+            .iter()
+            .filter(|t| t.attribs.iter().any(|a| is_attrib(a, attrib)))
+            .partition(|t| !is_strength(&t.aspect));
+        let cod_mods: Vec<f32> = cur.iter().map(|t| t.scale).collect();
+        if cod_mods.is_empty() && !strength.is_empty() {
+            report.push(format!(
+                "{} ({}): effect {} modifies {:?} but CoD only scales its strength",
+                p.full_name, p.display_name, e.unique_id, attrib
+            ));
+        } else if cod_mods.is_empty() {
+            report.push(format!(
+                "{} ({}): effect {} modifies {:?} but CoD has no matching attrib mod",
+                p.full_name, p.display_name, e.unique_id, attrib
+            ));
+        } else if cod_mods
+            .iter()
+            .all(|&m| tolerance(tolerances, attrib).compare(modified, m) == Match::Differs)
+        {
+            report.push(format!(
+                "{} ({}): effect {} atr_mod {:?} {} differs from CoD {:?}",
+                p.full_name, p.display_name, e.unique_id, attrib, modified, cod_mods
+            ));
+        }
+    }
+    (report, changed)
+}
@@ -0,0 +1,120 @@
+//! Powers in the Boosts group, which carry the effects of enhancements: their enhancement values,
+//! and which enhancement sets the enhancements belong to.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::{
+    cod,
+    mids::{self, enums::EffectType},
+    overrides::{Overrides, SyncField},
+    tolerance::{Match, Tolerance},
+};
+
+fn normalise(name: &str) -> String {
+    name.chars().filter(|&c| c != '_').collect::<String>().to_ascii_lowercase()
+}
+
+/// Fixes the scale of each Enhancement effect of the Boosts powers from the CoD template for the
+/// same attribute, where there's only one of each. `resolved` holds the CoD full name each Mids
+/// power was matched with, by position. Powers whose override skips everything are left alone, and
+/// locked powers are only reported. Returns a description of each fix and problem, and whether
+/// anything was changed.
+pub fn sync(
+    mdb: &mut mids::Database,
+    resolved: &[Option<String>],
+    cod_powers: &HashMap<String, cod::Power>,
+    tolerance: &Tolerance,
+    near_misses: &mut Vec<String>,
+    overrides: &Overrides,
+    force: bool,
+) -> (Vec<String>, bool) {
+    let mut report = Vec::new();
+    let mut changed = false;
+
+    for (p, cod_name) in mdb.powers.iter_mut().zip(resolved) {
+        if p.group_name != "Boosts" {
+            continue;
+        }
+        let Some(cod_p) = cod_name.as_ref().and_then(|n| cod_powers.get(n)) else {
+            continue;
+        };
+        if overrides.get(&p.full_name).is_some_and(|r| r.skips(SyncField::All)) {
+            continue;
+        }
+        let locked = p.never_auto_update && !force;
+        let templates = cod_p.templates();
+
+        // Effects by the attribute they enhance.
+        let mut by_attrib: BTreeMap<String, Vec<&mut mids::Effect>> = BTreeMap::new();
+        for e in p.effects.iter_mut().filter(|e| e.effect_type == EffectType::Enhancement) {
+            by_attrib.entry(normalise(&format!("{:?}", e.et_modifies))).or_default().push(e);
+        }
+        for (attrib, mut effects) in by_attrib {
+            let cod_scales: Vec<f32> = templates
+                .iter()
+                .filter(|t| t.attribs.iter().any(|a| normalise(a) == attrib))
+                .map(|t| t.scale)
+                .collect();
+            match (effects.as_mut_slice(), cod_scales.as_slice()) {
+                (_, []) => report.push(format!("{} ({}): enhances {} but CoD doesn't", p.full_name, p.display_name, attrib)),
+                ([e], [cod_scale]) => match tolerance.compare(e.scale, *cod_scale) {
+                    Match::Exact => {}
+                    Match::Near => near_misses.push(format!(
+                        "{} ({}): effect {} scale {} vs CoD {}",
+                        p.full_name, p.display_name, e.unique_id, e.scale, cod_scale
+                    )),
+                    Match::Differs if locked => report.push(format!(
+                        "{} ({}): effect {} scale {} differs from CoD {} but power is never_auto_update",
+                        p.full_name, p.display_name, e.unique_id, e.scale, cod_scale
+                    )),
+                    Match::Differs => {
+                        let expected = tolerance.expected(*cod_scale);
+                        report.push(format!(
+                            "{} ({}): effect {} scale {} fixed to {} [from {}]",
+                            p.full_name, p.display_name, e.unique_id, e.scale, expected, cod_p.full_name
+                        ));
+                        e.scale = expected;
+                        changed = true;
+                    }
+                },
+                (effects, cod_scales) => {
+                    let scales: Vec<f32> = effects.iter().map(|e| e.scale).collect();
+                    let unmatched = scales
+                        .iter()
+                        .any(|&s| cod_scales.iter().all(|&c| tolerance.compare(s, c) == Match::Differs));
+                    if unmatched {
+                        report.push(format!(
+                            "{} ({}): {} scales {:?} differ from CoD {:?}",
+                            p.full_name, p.display_name, attrib, scales, cod_scales
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    (report, changed)
+}
+
+/// Reports boosts of CoD sets that Mids has no Boosts power for.
+pub fn check_sets(mdb: &mids::Database, boost_sets: &[cod::BoostSet]) -> Vec<String> {
+    let mut report = Vec::new();
+    // Boosts powers are named after the boost, e.g. Boosts.Crafted_Accuracy_I.Crafted_Accuracy_I.
+    let mids_boosts: BTreeSet<String> = mdb
+        .powers
+        .iter()
+        .filter(|p| p.group_name == "Boosts")
+        .map(|p| p.set_name.to_ascii_lowercase())
+        .collect();
+    for set in boost_sets {
+        let members: BTreeSet<String> = set.boost_lists.iter().flatten().map(|b| b.to_ascii_lowercase()).collect();
+        // Sets with no pieces in Mids at all are assumed to be deliberately left out.
+        if !members.iter().any(|m| mids_boosts.contains(m)) {
+            continue;
+        }
+        for m in members.iter().filter(|m| !mids_boosts.contains(*m)) {
+            report.push(format!("Boost set {} ({}): no Boosts power for {}", set.name, set.display_name, m));
+        }
+    }
+    report
+}
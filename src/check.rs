@@ -0,0 +1,98 @@
+use crate::mids::{self, enums::EffectType};
+
+struct Checker {
+    problems: Vec<String>,
+}
+
+impl Checker {
+    /// Checks that an optional reference resolves, returning its position.
+    fn resolve(&mut self, owner: &str, field: &str, value: &str, found: Option<usize>, target: &str) -> Option<usize> {
+        if !value.is_empty() && found.is_none() {
+            self.problems
+                .push(format!("{}: {} {:?} does not match any {}", owner, field, value, target));
+        }
+        found
+    }
+
+    /// Checks that a numeric id agrees with the position its string id resolved to.
+    fn nid(&mut self, owner: &str, field: &str, nid: i32, found: Option<usize>) {
+        let expected = found.map_or(-1, |i| i as i32);
+        if nid != expected {
+            self.problems.push(format!("{}: {} is {} but should be {}", owner, field, nid, expected));
+        }
+    }
+}
+
+/// Checks the string cross-references in the database, and that numeric ids stored alongside them
+/// agree. Returns a description of each problem found.
+pub fn check(mdb: &mids::Database) -> Vec<String> {
+    let idx = mdb.indices();
+    let mut c = Checker { problems: Vec::new() };
+
+    for ps in &mdb.powersets {
+        let owner = &ps.full_name;
+        let at = c.resolve(owner, "at_class", &ps.at_class, idx.archetype(&ps.at_class), "archetype");
+        c.nid(owner, "nid_archetype", ps.nid_archetype, at);
+        c.resolve(owner, "uid_trunk_set", &ps.uid_trunk_set, idx.powerset(&ps.uid_trunk_set), "powerset");
+        c.resolve(
+            owner,
+            "uid_link_secondary",
+            &ps.uid_link_secondary,
+            idx.powerset(&ps.uid_link_secondary),
+            "powerset",
+        );
+        for m in &ps.mutex_sets {
+            let found = c.resolve(owner, "mutex_sets.uid", &m.uid, idx.powerset(&m.uid), "powerset");
+            c.nid(owner, &format!("mutex_sets[{}].nid", m.uid), m.nid, found);
+        }
+    }
+
+    for p in &mdb.powers {
+        let owner = &p.full_name;
+        for (field, ids) in [
+            ("requires.power_id", &p.requires.power_id),
+            ("requires.power_id_not", &p.requires.power_id_not),
+        ] {
+            for (a, b) in ids {
+                c.resolve(owner, field, a, idx.power(a), "power");
+                c.resolve(owner, field, b, idx.power(b), "power");
+            }
+        }
+        for s in &p.uid_sub_power {
+            c.resolve(owner, "uid_sub_power", s, idx.power(s), "power");
+        }
+        for e in &p.effects {
+            let owner = format!("{} effect {}", p.full_name, e.unique_id);
+            if !e.power_full_name.eq_ignore_ascii_case(&p.full_name) {
+                c.problems
+                    .push(format!("{}: power_full_name {:?} does not match its power", owner, e.power_full_name));
+            }
+            match e.effect_type {
+                EffectType::EntCreate => {
+                    c.resolve(&owner, "summon", &e.summon, idx.summon(&e.summon), "summon");
+                }
+                // These refer to powers rather than summons.
+                EffectType::PowerRedirect | EffectType::GrantPower => {
+                    let field = if e.override_.is_empty() { "summon" } else { "override" };
+                    let target = e.redirect_target();
+                    c.resolve(&owner, field, target, idx.power(target), "power");
+                }
+                _ => {}
+            }
+            let at = c.resolve(&owner, "uid_class_name", &e.uid_class_name, idx.archetype(&e.uid_class_name), "archetype");
+            c.nid(&owner, "n_id_class_name", e.n_id_class_name, at);
+        }
+    }
+
+    for s in &mdb.summons {
+        let owner = format!("summon {}", s.uid);
+        for ps in &s.powerset_full_name {
+            c.resolve(&owner, "powerset_full_name", ps, idx.powerset(ps), "powerset");
+        }
+        for p in &s.upgrade_power_full_name {
+            c.resolve(&owner, "upgrade_power_full_name", p, idx.power(p), "power");
+        }
+    }
+
+    c.problems
+}
@@ -1,10 +1,15 @@
 use std::{
     collections::HashMap,
-    io::{Read, Seek},
+    fs::{self, File},
+    hash::Hasher,
+    io::{self, BufReader, BufWriter, Read, Seek},
+    path::{Path, PathBuf},
+    thread,
+    time::UNIX_EPOCH,
 };
 
-use serde::{de::DeserializeOwned, Deserialize};
-use zip::ZipArchive;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use zip::{result::ZipError, ZipArchive};
 
 #[derive(Debug, Deserialize)]
 pub struct ArchetypesIndex {
@@ -47,6 +52,64 @@ pub struct Power {
     pub power_lifetime: f32,
     pub available_level: i32,
     pub redirect: Vec<Redirect>,
+    #[serde(default)]
+    pub effects: Vec<EffectGroup>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EffectGroup {
+    #[serde(default)]
+    pub templates: Vec<Template>,
+    #[serde(default)]
+    pub child_effects: Vec<EffectGroup>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Template {
+    pub attribs: Vec<String>,
+    #[serde(default)]
+    pub aspect: String,
+    #[serde(default)]
+    pub scale: f32,
+}
+
+impl Power {
+    /// All effect templates, including those of child effects.
+    pub fn templates(&self) -> Vec<&Template> {
+        fn walk<'a>(groups: &'a [EffectGroup], out: &mut Vec<&'a Template>) {
+            for g in groups {
+                out.extend(&g.templates);
+                walk(&g.child_effects, out);
+            }
+        }
+        let mut out = Vec::new();
+        walk(&self.effects, &mut out);
+        out
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BoostsIndex {
+    pub boosts: Vec<Boost>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Boost {
+    pub name: String,         // matches EClasses.mhd ClassID, e.g. "Accuracy_Boost"
+    pub display_name: String, // as used in Power.boosts_allowed, e.g. "Enhance Accuracy"
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BoostSetsIndex {
+    pub boost_sets: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BoostSet {
+    pub name: String,
+    pub display_name: String,
+    pub group_name: String, // category, as used in Power.allowed_boostset_cats
+    pub boost_lists: Vec<Vec<String>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -56,13 +119,160 @@ pub struct Redirect {
     pub show_in_info: bool,
 }
 
-pub struct Database<R: Read + Seek> {
-    pub zip: ZipArchive<R>,
+/// A critter definition, as summoned by EntCreate effects. Mids calls these summons.
+#[derive(Debug, Deserialize)]
+pub struct Entity {
+    #[serde(default)]
+    pub display_name: String,
+    #[serde(default)]
+    pub class_name: String,
+    #[serde(default)]
+    pub powerset_names: Vec<String>,
+}
+
+/// Backing store for the raw CoD data, addressed by '/'-separated paths relative to the data root.
+pub trait Storage: Send {
+    fn open(&mut self, path: &str) -> anyhow::Result<Box<dyn Read + '_>>;
+    /// Opens an independent handle on the same data, e.g. for another thread.
+    fn reopen(&self) -> anyhow::Result<Box<dyn Storage>>;
+}
+
+/// A zip of the raw data.
+pub struct Zip {
+    pub path: PathBuf,
+    pub archive: ZipArchive<File>,
+}
+
+impl Storage for Zip {
+    fn open(&mut self, path: &str) -> anyhow::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(self.archive.by_name(path)?))
+    }
+
+    fn reopen(&self) -> anyhow::Result<Box<dyn Storage>> {
+        Ok(Box::new(Zip {
+            path: self.path.clone(),
+            archive: ZipArchive::new(File::open(&self.path)?)?,
+        }))
+    }
+}
+
+/// An extracted copy of the raw data.
+pub struct Directory {
+    pub root: PathBuf,
+}
+
+impl Storage for Directory {
+    fn open(&mut self, path: &str) -> anyhow::Result<Box<dyn Read + '_>> {
+        let full = path.split('/').fold(self.root.clone(), |p, c| p.join(c));
+        Ok(Box::new(BufReader::new(File::open(full)?)))
+    }
+
+    fn reopen(&self) -> anyhow::Result<Box<dyn Storage>> {
+        Ok(Box::new(Directory { root: self.root.clone() }))
+    }
+}
+
+/// Returns true if the error is the storage reporting a missing file.
+pub fn is_not_found(e: &anyhow::Error) -> bool {
+    match e.downcast_ref::<ZipError>() {
+        Some(ZipError::FileNotFound) => true,
+        _ => e.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::NotFound),
+    }
 }
 
-impl<R: Read + Seek> Database<R> {
+/// Maps powerset full name to (power display name -> full names of every power with that display name).
+pub type Index = HashMap<String, HashMap<String, Vec<String>>>;
+
+#[derive(Deserialize, Serialize)]
+struct IndexCache {
+    revision: String,
+    fingerprint: Option<u64>,
+    index: Index,
+}
+
+pub struct Database {
+    pub storage: Box<dyn Storage>,
+    /// Hash of the zip contents, or of the file names, sizes and modification times of a directory.
+    pub fingerprint: Option<u64>,
+}
+
+/// 64-bit FNV-1a. Unlike DefaultHasher, its output is stable across Rust releases, so it's safe to
+/// persist in the index cache.
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= u64::from(b);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Hashes the names, sizes and modification times of every file under `dir`, so that edits to an
+/// extracted checkout invalidate the index cache.
+fn fingerprint_dir(dir: &Path, hasher: &mut Fnv) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    for e in entries {
+        let meta = e.metadata()?;
+        hasher.write(e.file_name().as_encoded_bytes());
+        if meta.is_dir() {
+            fingerprint_dir(&e.path(), hasher)?;
+            continue;
+        }
+        let mtime = meta.modified()?.duration_since(UNIX_EPOCH).unwrap_or_default();
+        hasher.write(&meta.len().to_le_bytes());
+        hasher.write(&mtime.as_secs().to_le_bytes());
+        hasher.write(&mtime.subsec_nanos().to_le_bytes());
+    }
+    Ok(())
+}
+
+impl Database {
+    /// Opens either a zip of the raw data or a directory it was extracted to.
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        if path.is_dir() {
+            let mut hasher = Fnv::new();
+            fingerprint_dir(path, &mut hasher)?;
+            return Ok(Database {
+                storage: Box::new(Directory { root: path.to_owned() }),
+                fingerprint: Some(hasher.finish()),
+            });
+        }
+        let mut f = File::open(path)?;
+        let mut hasher = Fnv::new();
+        let mut buf = vec![0u8; 1 << 16];
+        loop {
+            let n = f.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.write(&buf[..n]);
+        }
+        f.rewind()?;
+        Ok(Database {
+            storage: Box::new(Zip {
+                path: path.to_owned(),
+                archive: ZipArchive::new(f)?,
+            }),
+            fingerprint: Some(hasher.finish()),
+        })
+    }
+
     pub fn load_json<T: DeserializeOwned>(&mut self, path: impl AsRef<str>) -> anyhow::Result<T> {
-        Ok(serde_json::from_reader(self.zip.by_name(path.as_ref())?)?)
+        Ok(serde_json::from_reader(self.storage.open(path.as_ref())?)?)
     }
 
     pub fn revision(&mut self) -> anyhow::Result<String> {
@@ -79,7 +289,116 @@ impl<R: Read + Seek> Database<R> {
         self.load_json(path)
     }
 
-    pub fn index(&mut self) -> anyhow::Result<HashMap<String, HashMap<String, String>>> {
+    /// Loads many powers at once, spread across one storage handle per thread. Powers that don't
+    /// exist are left out of the result.
+    pub fn load_powers(&self, names: &[&str]) -> anyhow::Result<HashMap<String, Power>> {
+        self.load_many(names, "power", |db, name| db.load_power(name))
+    }
+
+    pub fn load_entity(&mut self, name: impl AsRef<str>) -> anyhow::Result<Entity> {
+        self.load_json(format!("entities/{}.json", name.as_ref().to_ascii_lowercase()))
+    }
+
+    /// Like load_powers(), for entities.
+    pub fn load_entities(&self, names: &[&str]) -> anyhow::Result<HashMap<String, Entity>> {
+        self.load_many(names, "entity", |db, name| db.load_entity(name))
+    }
+
+    fn load_many<T: Send>(
+        &self,
+        names: &[&str],
+        what: &str,
+        load: fn(&mut Database, &str) -> anyhow::Result<T>,
+    ) -> anyhow::Result<HashMap<String, T>> {
+        let threads = thread::available_parallelism().map_or(1, usize::from);
+        let chunk_size = names.len().div_ceil(threads).max(1);
+        let mut map = HashMap::with_capacity(names.len());
+        let mut dbs = Vec::with_capacity(threads);
+        for _ in names.chunks(chunk_size) {
+            dbs.push(Database {
+                storage: self.storage.reopen()?,
+                fingerprint: self.fingerprint,
+            });
+        }
+        thread::scope(|scope| {
+            let workers: Vec<_> = names
+                .chunks(chunk_size)
+                .zip(dbs)
+                .map(|(chunk, mut db)| {
+                    scope.spawn(move || -> anyhow::Result<Vec<(String, T)>> {
+                        let mut loaded = Vec::with_capacity(chunk.len());
+                        for &name in chunk {
+                            match load(&mut db, name) {
+                                Ok(v) => loaded.push((name.to_string(), v)),
+                                Err(e) if is_not_found(&e) => {}
+                                Err(e) => return Err(e.context(format!("loading {} {}", what, name))),
+                            }
+                        }
+                        Ok(loaded)
+                    })
+                })
+                .collect();
+            for w in workers {
+                map.extend(w.join().expect("loader panicked")?);
+            }
+            Ok(map)
+        })
+    }
+
+    pub fn load_boosts(&mut self) -> anyhow::Result<Vec<Boost>> {
+        Ok(self.load_json::<BoostsIndex>("boosts/index.json")?.boosts)
+    }
+
+    pub fn load_boost_set(&mut self, name: impl AsRef<str>) -> anyhow::Result<BoostSet> {
+        self.load_json(format!("boost_sets/{}.json", name.as_ref().to_ascii_lowercase()))
+    }
+
+    /// Loads every boost set, in index order.
+    pub fn load_boost_sets(&mut self) -> anyhow::Result<Vec<BoostSet>> {
+        let bi: BoostSetsIndex = self.load_json("boost_sets/index.json")?;
+        let names: Vec<&str> = bi.boost_sets.iter().map(String::as_str).collect();
+        let mut loaded = self.load_many(&names, "boost set", |db, name| db.load_boost_set(name))?;
+        names
+            .iter()
+            .map(|&n| {
+                loaded
+                    .remove(n)
+                    .ok_or_else(|| anyhow::anyhow!("boost set {} is in the index but missing", n))
+            })
+            .collect()
+    }
+
+    /// Like index(), but reuses the copy cached at `cache_path` if it was built from the same data.
+    pub fn index_cached(&mut self, cache_path: impl AsRef<Path>) -> anyhow::Result<Index> {
+        let cache_path = cache_path.as_ref();
+        let revision = self.revision()?;
+        if let Ok(f) = File::open(cache_path) {
+            match serde_json::from_reader::<_, IndexCache>(BufReader::new(f)) {
+                Ok(c) if c.revision == revision && c.fingerprint == self.fingerprint => return Ok(c.index),
+                Ok(_) => {}
+                Err(e) => println!("Ignoring unreadable index cache {}: {}", cache_path.display(), e),
+            }
+        }
+        let cache = IndexCache {
+            revision,
+            fingerprint: self.fingerprint,
+            index: self.index()?,
+        };
+        // The cache is only an optimisation, so failing to write it isn't fatal.
+        let write = || -> anyhow::Result<()> {
+            if let Some(dir) = cache_path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            serde_json::to_writer(BufWriter::new(File::create(cache_path)?), &cache)?;
+            Ok(())
+        };
+        if let Err(e) = write() {
+            println!("Couldn't write index cache {}: {}", cache_path.display(), e);
+        }
+        Ok(cache.index)
+    }
+
+    pub fn index(&mut self) -> anyhow::Result<Index> {
         let mut map = HashMap::new();
         let pi: PowersIndex = self.load_json("powers/index.json")?;
         for mut cat in pi.power_categories {
@@ -93,9 +412,8 @@ impl<R: Read + Seek> Database<R> {
                 let ps: Powerset = self.load_json(format!("powers/{}/{}/index.json", cat, set_dir))?;
                 let mut submap = HashMap::new();
                 for (display_name, power_name) in ps.power_display_names.into_iter().zip(ps.power_names) {
-                    submap.entry(display_name).and_modify(String::clear).or_insert(power_name);
+                    submap.entry(display_name).or_insert_with(Vec::new).push(power_name);
                 }
-                submap.retain(|_k, v| !v.is_empty());
                 map.insert(set, submap);
             }
         }
@@ -0,0 +1,95 @@
+use std::{collections::HashMap, fmt};
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{
+    merge::{key_of, show_field, KEYED_LISTS},
+    mids,
+};
+
+/// A difference at `path`, where None means the record is absent on that side. Field values are
+/// shown as text, as mids::fields does.
+#[derive(Debug, Serialize)]
+pub struct Change {
+    pub path: String,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.old, &self.new) {
+            (Some(old), Some(new)) => write!(f, "{}: {} -> {}", self.path, text(old), text(new)),
+            (None, _) => write!(f, "{}: added", self.path),
+            (_, None) => write!(f, "{}: removed", self.path),
+        }
+    }
+}
+
+fn text(v: &Value) -> String {
+    match v {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn object(changes: &mut Vec<Change>, path: &str, old: &Map<String, Value>, new: &Map<String, Value>) {
+    for (k, o) in old {
+        let n = &new[k];
+        let field_path = if path.is_empty() { k.clone() } else { format!("{}.{}", path, k) };
+        match (KEYED_LISTS.iter().find(|(name, _)| name == k), o, n) {
+            (Some((_, key)), Value::Array(o), Value::Array(n)) => list(changes, &field_path, key, o, n),
+            (_, Value::Object(o), Value::Object(n)) => object(changes, &field_path, o, n),
+            _ if o != n => changes.push(Change {
+                path: field_path,
+                old: Some(o.clone()),
+                new: Some(n.clone()),
+            }),
+            _ => {}
+        }
+    }
+}
+
+fn list(changes: &mut Vec<Change>, path: &str, key: &str, old: &[Value], new: &[Value]) {
+    let by_key = |list: &[Value]| -> HashMap<String, usize> { list.iter().enumerate().map(|(i, v)| (key_of(v, key), i)).collect() };
+    let (o, n) = (by_key(old), by_key(new));
+    for v in old {
+        let k = key_of(v, key);
+        let item_path = format!("{}[{}]", path, k);
+        match (v, n.get(&k).map(|&i| &new[i])) {
+            (Value::Object(a), Some(Value::Object(b))) => object(changes, &item_path, a, b),
+            (_, None) => changes.push(Change {
+                path: item_path,
+                old: Some(v.clone()),
+                new: None,
+            }),
+            _ => {}
+        }
+    }
+    for v in new {
+        let k = key_of(v, key);
+        if !o.contains_key(&k) {
+            changes.push(Change {
+                path: format!("{}[{}]", path, k),
+                old: None,
+                new: Some(v.clone()),
+            });
+        }
+    }
+}
+
+/// Compares two databases field by field. Archetypes, powersets, powers, effects and summons are
+/// matched by name (or unique_id for effects) rather than position.
+pub fn diff(old: &mids::Database, new: &mids::Database) -> anyhow::Result<Vec<Change>> {
+    let (Value::Object(o), Value::Object(n)) = (serde_json::to_value(old)?, serde_json::to_value(new)?) else {
+        unreachable!()
+    };
+    let mut changes = Vec::new();
+    object(&mut changes, "", &o, &n);
+    for c in &mut changes {
+        show_field(old, &c.path, &mut c.old);
+        show_field(new, &c.path, &mut c.new);
+    }
+    Ok(changes)
+}
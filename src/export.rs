@@ -0,0 +1,73 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+use anyhow::{bail, Context, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::mids;
+
+fn write_json(path: &Path, value: &impl Serialize) -> Result<()> {
+    let f = File::create(path).with_context(|| format!("creating {}", path.display()))?;
+    serde_json::to_writer_pretty(BufWriter::new(f), value)?;
+    Ok(())
+}
+
+pub(crate) fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let f = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    serde_json::from_reader(BufReader::new(f)).with_context(|| format!("parsing {}", path.display()))
+}
+
+/// Writes the database as a single pretty-printed JSON file. Enums are written by name, and flag
+/// sets as lists of names.
+pub fn export_file(mdb: &mids::Database, path: &Path) -> Result<()> {
+    write_json(path, mdb)
+}
+
+/// Writes the database as a directory holding database.json, with each power split out into
+/// powers/{full_name}.json. database.json lists the power names in order in place of the powers.
+pub fn export_dir(mdb: &mids::Database, dir: &Path) -> Result<()> {
+    let powers_dir = dir.join("powers");
+    fs::create_dir_all(&powers_dir)?;
+    let mut seen = HashSet::new();
+    let mut names = Vec::with_capacity(mdb.powers.len());
+    for p in &mdb.powers {
+        // File names may be case-insensitive.
+        if !seen.insert(p.full_name.to_ascii_lowercase()) {
+            bail!("can't split powers: duplicate full_name {:?}", p.full_name);
+        }
+        write_json(&powers_dir.join(format!("{}.json", p.full_name)), p)?;
+        names.push(Value::String(p.full_name.clone()));
+    }
+    // Goes through text rather than serde_json::to_value, which would widen f32 values to f64
+    // and write them with spurious digits.
+    let Value::Object(mut db) = serde_json::from_str(&serde_json::to_string(mdb)?)? else {
+        unreachable!()
+    };
+    db.insert("powers".to_string(), Value::Array(names));
+    write_json(&dir.join("database.json"), &db)
+}
+
+/// Reads a database written by export_file or export_dir.
+pub fn import(path: &Path) -> Result<mids::Database> {
+    if !path.is_dir() {
+        return read_json(path);
+    }
+    let mut db: serde_json::Map<String, Value> = read_json(&path.join("database.json"))?;
+    let Some(Value::Array(names)) = db.remove("powers") else {
+        bail!("{}: missing powers list", path.display());
+    };
+    let mut powers = Vec::with_capacity(names.len());
+    for n in names {
+        let Value::String(n) = n else {
+            bail!("{}: powers list entry {} isn't a name", path.display(), n);
+        };
+        powers.push(read_json::<Value>(&path.join("powers").join(format!("{}.json", n)))?);
+    }
+    db.insert("powers".to_string(), Value::Array(powers));
+    Ok(serde_json::from_value(Value::Object(db))?)
+}
@@ -0,0 +1,137 @@
+//! Incarnate powers, which follow their own rules: every power in a slot unlocks at the slot's
+//! level, and each slot has powers in four tiers.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+    cod, mids,
+    overrides::{Overrides, SyncField},
+};
+
+/// The tier of an Incarnate power (1-4), from the naming scheme shared by Mids and CoD short names,
+/// e.g. Agility_Boost (1), Agility_Radial_Boost (2), Agility_Total_Radial_Revamp (3),
+/// Agility_Radial_Paragon (4).
+pub fn tier(short_name: &str) -> u8 {
+    let lower = short_name.to_ascii_lowercase();
+    let words: Vec<&str> = lower.split('_').collect();
+    let has = |ws: &[&str]| words.iter().any(|w| ws.contains(w));
+    if has(&["paragon", "final", "flawless", "superior", "epiphany", "genome"]) {
+        4
+    } else if has(&["partial", "total"]) {
+        3
+    } else if has(&["core", "radial"]) {
+        2
+    } else {
+        1
+    }
+}
+
+/// The CoD powerset for each Mids Incarnate slot, by Mids slot full name.
+fn slots<'a>(mdb: &mids::Database, cidx: &'a cod::Index, powerset_map: &BTreeMap<&str, &str>) -> BTreeMap<String, &'a str> {
+    let mut slots = BTreeMap::new();
+    for ps in mdb.powersets.iter().filter(|ps| ps.full_name.split('.').next() == Some("Incarnate")) {
+        let nl = ps.full_name.to_ascii_lowercase();
+        let cod_name = powerset_map.get(nl.as_str()).copied().unwrap_or(&nl);
+        if let Some((name, _)) = cidx.get_key_value(cod_name) {
+            slots.insert(ps.full_name.clone(), name.as_str());
+        }
+    }
+    slots
+}
+
+/// Every CoD power in the Incarnate slots, so that they can be loaded up front.
+pub fn cod_names<'a>(mdb: &mids::Database, cidx: &'a cod::Index, powerset_map: &BTreeMap<&str, &str>) -> Vec<&'a str> {
+    slots(mdb, cidx, powerset_map)
+        .values()
+        .flat_map(|set| cidx[*set].values().flatten())
+        .map(String::as_str)
+        .collect()
+}
+
+/// Fixes the level of each Incarnate power to its slot's level, which is the lowest CoD level of
+/// the slot's powers, and checks each power's tier against the CoD power it was synced from.
+/// `resolved` holds the CoD full name each Mids power was matched with, by position. Levels of
+/// locked powers, and of powers whose override skips or sets the level, are left alone. Returns a
+/// description of each fix and problem, and whether anything was changed.
+pub fn sync(
+    mdb: &mut mids::Database,
+    resolved: &[Option<String>],
+    cod_powers: &HashMap<String, cod::Power>,
+    cidx: &cod::Index,
+    powerset_map: &BTreeMap<&str, &str>,
+    overrides: &Overrides,
+    force: bool,
+) -> (Vec<String>, bool) {
+    let mut report = Vec::new();
+    let mut changed = false;
+    for (slot, cod_set) in slots(mdb, cidx, powerset_map) {
+        let cod_slot: Vec<&cod::Power> = cidx[cod_set].values().flatten().filter_map(|n| cod_powers.get(n)).collect();
+        let Some(level) = cod_slot.iter().map(|p| p.available_level + 1).min() else {
+            report.push(format!("{}: no CoD powers in {}", slot, cod_set));
+            continue;
+        };
+
+        let prefix = format!("{}.", slot);
+        let mut matched = Vec::new();
+        for (p, cod_name) in mdb.powers.iter_mut().zip(resolved) {
+            if !p.full_name.starts_with(&prefix) {
+                continue;
+            }
+            let Some(cod_p) = cod_name.as_ref().and_then(|n| cod_powers.get(n)) else {
+                report.push(format!("{} ({}): no CoD power in {}", p.full_name, p.display_name, cod_set));
+                continue;
+            };
+            matched.push(cod_p.full_name.to_ascii_lowercase());
+
+            let cod_short = cod_p.full_name.rsplit('.').next().unwrap_or_default();
+            let (t, cod_t) = (tier(&p.power_name), tier(cod_short));
+            if t != cod_t {
+                report.push(format!(
+                    "{} ({}): tier {} but CoD {} is tier {}",
+                    p.full_name, p.display_name, t, cod_p.full_name, cod_t
+                ));
+            }
+
+            let rule = overrides.get(&p.full_name);
+            // An override level was already applied by the main sync.
+            if p.level == level || rule.is_some_and(|r| r.level.is_some()) {
+                continue;
+            }
+            if p.never_auto_update && !force {
+                report.push(format!(
+                    "{}: slot level {} differs from {} but power is never_auto_update",
+                    p.full_name, level, p.level
+                ));
+            } else if rule.is_some_and(|r| r.skips(SyncField::Level)) {
+                report.push(format!(
+                    "{}: slot level {} differs from {} but override skips it",
+                    p.full_name, level, p.level
+                ));
+            } else {
+                report.push(format!(
+                    "{}: level {} fixed to slot level {} [from {}]",
+                    p.full_name, p.level, level, cod_set
+                ));
+                p.level = level;
+                changed = true;
+            }
+        }
+
+        let mut missing: Vec<&cod::Power> = cod_slot
+            .into_iter()
+            .filter(|c| !matched.contains(&c.full_name.to_ascii_lowercase()))
+            .collect();
+        missing.sort_unstable_by(|a, b| a.full_name.cmp(&b.full_name));
+        for c in missing {
+            let short = c.full_name.rsplit('.').next().unwrap_or_default();
+            report.push(format!(
+                "{}: no Mids power for tier {} CoD power {} ({})",
+                slot,
+                tier(short),
+                c.full_name,
+                c.display_name
+            ));
+        }
+    }
+    (report, changed)
+}
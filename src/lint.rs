@@ -0,0 +1,52 @@
+use crate::mids;
+
+/// Checks the Power fields that should be derived from others, returning a description of each
+/// disagreement, and whether anything was rewritten. If `fix` is set, the derived fields are also
+/// rewritten from their sources.
+///
+/// Power::level is derived too, but from the CoD available_level, which Mids doesn't keep; only the
+/// CoD sync can check it.
+pub fn lint(mdb: &mut mids::Database, eclasses: &[Option<String>], fix: bool) -> (Vec<String>, bool) {
+    let mut problems = Vec::new();
+    let mut changed = false;
+    for p in &mut mdb.powers {
+        let mut parts = p.full_name.split('.');
+        let (group, set) = (parts.next().unwrap_or_default(), parts.next().unwrap_or_default());
+        if p.group_name != group || p.set_name != set {
+            problems.push(format!(
+                "{}: group_name/set_name {:?}/{:?} should be {:?}/{:?}",
+                p.full_name, p.group_name, p.set_name, group, set
+            ));
+            if fix {
+                p.group_name = group.to_string();
+                p.set_name = set.to_string();
+                changed = true;
+            }
+        }
+
+        if p.base_recharge_time != p.recharge_time {
+            problems.push(format!(
+                "{}: base_recharge_time {} should be {}",
+                p.full_name, p.base_recharge_time, p.recharge_time
+            ));
+            if fix {
+                p.base_recharge_time = p.recharge_time;
+                changed = true;
+            }
+        }
+
+        let boosts: Option<Vec<String>> = p.enhancements.iter().map(|&e| eclasses.get(e as usize).cloned().flatten()).collect();
+        match boosts {
+            Some(boosts) if p.boosts_allowed != boosts => {
+                problems.push(format!("{}: boosts_allowed {:?} should be {:?}", p.full_name, p.boosts_allowed, boosts));
+                if fix {
+                    p.boosts_allowed = boosts;
+                    changed = true;
+                }
+            }
+            Some(_) => {}
+            None => problems.push(format!("{}: enhancements {:?} not all in EClasses.mhd", p.full_name, p.enhancements)),
+        }
+    }
+    (problems, changed)
+}
@@ -2,18 +2,37 @@
 
 use std::{
     collections::{BTreeMap, BTreeSet},
+    ffi::OsString,
     fs::File,
     io::{BufRead, BufReader, BufWriter},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    time::SystemTime,
 };
 
 use anyhow::{bail, Context, Result};
 use native_windows_gui as nwg;
-use zip::{read::ZipArchive, result::ZipError};
+use tolerance::Match;
 
+mod attribmod;
+mod boosts;
+mod check;
 mod cod;
+mod diff;
+mod export;
+mod incarnate;
+mod lint;
+mod merge;
 mod mids;
+mod migrate;
 mod netbinary;
+mod overrides;
+mod patch;
+mod query;
+mod rename;
+mod summons;
+mod table;
+mod tolerance;
+mod version;
 
 fn prompt_path(title: &str, filter: &str) -> Result<PathBuf, anyhow::Error> {
     let mut fd = nwg::FileDialog::default();
@@ -28,7 +47,7 @@ fn prompt_path(title: &str, filter: &str) -> Result<PathBuf, anyhow::Error> {
     Ok(PathBuf::from(fd.get_selected_item()?))
 }
 
-fn prompt_save(title: &str, filter: &str) -> Result<BufWriter<File>> {
+fn prompt_save_path(title: &str, filter: &str) -> Result<PathBuf> {
     let mut fd = nwg::FileDialog::default();
     nwg::FileDialog::builder()
         .title(title)
@@ -38,52 +57,384 @@ fn prompt_save(title: &str, filter: &str) -> Result<BufWriter<File>> {
     if !fd.run::<nwg::ControlHandle>(None) {
         bail!("user canceled prompt");
     }
-    Ok(BufWriter::new(File::create(fd.get_selected_item()?)?))
+    Ok(PathBuf::from(fd.get_selected_item()?))
+}
+
+fn prompt_save(title: &str, filter: &str) -> Result<BufWriter<File>> {
+    Ok(BufWriter::new(File::create(prompt_save_path(title, filter)?)?))
+}
+
+/// Removes `flag` from `args`, returning whether it was present.
+fn take_flag(args: &mut Vec<OsString>, flag: &str) -> bool {
+    let len = args.len();
+    args.retain(|a| a != flag);
+    args.len() != len
+}
+
+/// Returns the path to the Mids database, prompting for one if not given.
+fn mids_path(path: Option<&OsString>) -> Result<PathBuf> {
+    match path {
+        Some(p) => Ok(PathBuf::from(p)),
+        None => prompt_path("Locate mids data", "Mids Reborn Database (I12.mhd)"),
+    }
+}
+
+fn load_mids(path: &Path) -> Result<mids::Database> {
+    let f = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    Ok(mids::from_reader(BufReader::new(f))?)
+}
+
+/// Loads the enhancement class names from the EClasses.mhd next to the Mids database, indexed by
+/// their position in Power::enhancements.
+fn load_eclasses(mids_path: &Path) -> Result<Vec<Option<String>>> {
+    let mut eclasses = Vec::new();
+    let f = File::open(mids_path.join("../EClasses.mhd"))?;
+    let mut lines = BufReader::new(f).lines();
+    for line in &mut lines {
+        if line?.starts_with("Index\t") {
+            break;
+        }
+    }
+    for line in lines {
+        let line = line?;
+        if line.starts_with("End") {
+            break;
+        }
+        let fields: Vec<&str> = line.split("\t").collect();
+        let index: usize = fields[0].parse().with_context(|| format!("line = {:?}", line))?;
+        if index >= eclasses.len() {
+            eclasses.resize(index + 1, None);
+        }
+        eclasses[index] = Some(fields[3].to_string());
+    }
+    Ok(eclasses)
+}
+
+/// Removes `--name=value` from `args`, returning the value if present.
+fn take_option(args: &mut Vec<OsString>, name: &str) -> Result<Option<String>> {
+    let prefix = format!("{}=", name);
+    let Some(i) = args.iter().position(|a| a.to_str().is_some_and(|a| a.starts_with(&prefix))) else {
+        return Ok(None);
+    };
+    let arg = args.remove(i).into_string().unwrap();
+    if args.iter().any(|a| a.to_str().is_some_and(|a| a.starts_with(&prefix))) {
+        bail!("{} given more than once", name);
+    }
+    Ok(Some(arg[prefix.len()..].to_string()))
 }
 
 fn main() -> Result<()> {
     nwg::init().unwrap();
 
-    let mids_path = prompt_path("Locate mids data", "Mids Reborn Database (I12.mhd)")?;
-    let zipf = File::open(prompt_path("Locate CoD raw data", "Zip File (*.zip)")?)?;
+    let mut args: Vec<OsString> = std::env::args_os().skip(1).collect();
+    match args.first().and_then(|a| a.to_str()) {
+        Some("check") => check_command(args.split_off(1)),
+        Some("lint") => lint_command(args.split_off(1)),
+        Some("diff") => diff_command(args.split_off(1)),
+        Some("merge") => merge_command(args.split_off(1)),
+        Some("export") => export_command(args.split_off(1)),
+        Some("import") => import_command(args.split_off(1)),
+        Some("export-table") => export_table_command(args.split_off(1)),
+        Some("import-table") => import_table_command(args.split_off(1)),
+        Some("query") => query_command(args.split_off(1)),
+        Some("patch") => patch_command(args.split_off(1)),
+        _ => sync(args),
+    }
+}
 
-    let mut eclasses = Vec::new();
-    let mut eclasses_map = BTreeMap::new();
-    {
-        let f = File::open(mids_path.join("../EClasses.mhd"))?;
-        let mut lines = BufReader::new(f).lines();
-        for line in &mut lines {
-            if line?.starts_with("Index\t") {
-                break;
-            }
+/// mids-sync check [--fix-nids] [I12.mhd]
+fn check_command(mut args: Vec<OsString>) -> Result<()> {
+    let fix_nids = take_flag(&mut args, "--fix-nids");
+    let mut mdb = load_mids(&mids_path(args.first())?)?;
+    if fix_nids {
+        let changes = mdb.rebuild_nids();
+        for c in &changes {
+            println!("{}", c);
         }
-        for line in lines {
-            let line = line?;
-            if line.starts_with("End") {
-                break;
-            }
-            let fields: Vec<&str> = line.split("\t").collect();
-            let index: usize = fields[0].parse().with_context(|| format!("line = {:?}", line))?;
-            if index >= eclasses.len() {
-                eclasses.resize(index + 1, None);
-            }
-            eclasses[index] = Some(fields[3].to_string());
+        if !changes.is_empty() {
+            let w = prompt_save("Save location", "Mids Reborn Database (*.mhd)")?;
+            mids::to_writer(w, &mdb)?;
         }
-        for (i, v) in eclasses.iter().enumerate() {
-            if let Some(v) = v {
-                eclasses_map.insert(v.as_str(), u32::try_from(i)?);
-            }
+    }
+    let problems = check::check(&mdb);
+    for p in &problems {
+        println!("{}", p);
+    }
+    if !problems.is_empty() {
+        bail!("{} problems found", problems.len());
+    }
+    println!("No problems found");
+    Ok(())
+}
+
+/// mids-sync lint [--fix] [I12.mhd]
+fn lint_command(mut args: Vec<OsString>) -> Result<()> {
+    let fix = take_flag(&mut args, "--fix");
+    let path = mids_path(args.first())?;
+    let eclasses = load_eclasses(&path)?;
+    let mut mdb = load_mids(&path)?;
+    let (problems, changed) = lint::lint(&mut mdb, &eclasses, fix);
+    for p in &problems {
+        println!("{}", p);
+    }
+    if changed {
+        let w = prompt_save("Save location", "Mids Reborn Database (*.mhd)")?;
+        mids::to_writer(w, &mdb)?;
+    }
+    if problems.is_empty() {
+        println!("No problems found");
+    } else if !fix {
+        bail!("{} problems found", problems.len());
+    }
+    Ok(())
+}
+
+/// mids-sync diff [--json] OLD NEW
+fn diff_command(mut args: Vec<OsString>) -> Result<()> {
+    let json = take_flag(&mut args, "--json");
+    let [old, new] = args.as_slice() else {
+        bail!("usage: mids-sync diff [--json] OLD NEW");
+    };
+    let changes = diff::diff(&load_mids(Path::new(old))?, &load_mids(Path::new(new))?)?;
+    if json {
+        serde_json::to_writer_pretty(std::io::stdout().lock(), &changes)?;
+        println!();
+    } else {
+        for c in &changes {
+            println!("{}", c);
+        }
+    }
+    Ok(())
+}
+
+/// mids-sync merge BASE OURS THEIRS
+fn merge_command(args: Vec<OsString>) -> Result<()> {
+    let [base, ours, theirs] = args.as_slice() else {
+        bail!("usage: mids-sync merge BASE OURS THEIRS");
+    };
+    let (base, ours, theirs) = (load_mids(Path::new(base))?, load_mids(Path::new(ours))?, load_mids(Path::new(theirs))?);
+    let (mut merged, conflicts) = merge::merge(&base, &ours, &theirs)?;
+    for c in merged.rebuild_nids() {
+        println!("{}", c);
+    }
+    for c in &conflicts {
+        println!("conflict at {} (kept ours)", c.path);
+    }
+    let path = prompt_save_path("Save location", "Mids Reborn Database (*.mhd)")?;
+    mids::to_writer(BufWriter::new(File::create(&path)?), &merged)?;
+    if !conflicts.is_empty() {
+        let report = path.with_extension("conflicts.json");
+        serde_json::to_writer_pretty(BufWriter::new(File::create(&report)?), &conflicts)?;
+        println!("{} conflicts written to {}", conflicts.len(), report.display());
+    }
+    Ok(())
+}
+
+/// mids-sync export [--split] I12.mhd OUT
+fn export_command(mut args: Vec<OsString>) -> Result<()> {
+    let split = take_flag(&mut args, "--split");
+    let [input, out] = args.as_slice() else {
+        bail!("usage: mids-sync export [--split] I12.mhd OUT");
+    };
+    let out = Path::new(out);
+    let mdb = load_mids(Path::new(input))?;
+    if split {
+        export::export_dir(&mdb, out)?;
+    } else {
+        export::export_file(&mdb, out)?;
+    }
+
+    // Make sure importing gives back exactly what we started with.
+    let mut original = Vec::new();
+    mids::to_writer(&mut original, &mdb)?;
+    let mut reimported = Vec::new();
+    mids::to_writer(&mut reimported, &export::import(out)?)?;
+    if original != reimported {
+        bail!("{} does not round-trip to an identical database", out.display());
+    }
+    Ok(())
+}
+
+/// mids-sync import IN OUT.mhd
+fn import_command(args: Vec<OsString>) -> Result<()> {
+    let [input, out] = args.as_slice() else {
+        bail!("usage: mids-sync import IN OUT.mhd");
+    };
+    let mdb = export::import(Path::new(input))?;
+    mids::to_writer(BufWriter::new(File::create(out)?), &mdb)?;
+    Ok(())
+}
+
+/// mids-sync export-table [--tsv] [--columns=a,b,...] I12.mhd OUT
+fn export_table_command(mut args: Vec<OsString>) -> Result<()> {
+    let delimiter = if take_flag(&mut args, "--tsv") { b'\t' } else { b',' };
+    let columns = take_option(&mut args, "--columns")?;
+    let columns: Vec<&str> = match &columns {
+        Some(c) => c.split(',').collect(),
+        None => table::DEFAULT_COLUMNS.to_vec(),
+    };
+    let [input, out] = args.as_slice() else {
+        bail!("usage: mids-sync export-table [--tsv] [--columns=a,b,...] I12.mhd OUT");
+    };
+    let mdb = load_mids(Path::new(input))?;
+    table::export(&mdb, &columns, delimiter, BufWriter::new(File::create(out)?))
+}
+
+/// mids-sync import-table [--tsv] I12.mhd IN
+fn import_table_command(mut args: Vec<OsString>) -> Result<()> {
+    let delimiter = if take_flag(&mut args, "--tsv") { b'\t' } else { b',' };
+    let [input, table_path] = args.as_slice() else {
+        bail!("usage: mids-sync import-table [--tsv] I12.mhd IN");
+    };
+    let mut mdb = load_mids(Path::new(input))?;
+    let changes = table::import(&mut mdb, delimiter, BufReader::new(File::open(table_path)?))?;
+    for c in &changes {
+        println!("{}", c);
+    }
+    if changes.is_empty() {
+        println!("No changes");
+        return Ok(());
+    }
+    let w = prompt_save("Save location", "Mids Reborn Database (*.mhd)")?;
+    mids::to_writer(w, &mdb)?;
+    Ok(())
+}
+
+fn run_query<'a, T: mids::fields::Fields + serde::Serialize + 'a>(
+    records: impl Iterator<Item = &'a T>,
+    key: &[&'static str],
+    expr: &str,
+    columns: Option<&str>,
+    json: bool,
+) -> Result<()> {
+    let expr = query::parse::<T>(expr)?;
+    let columns = match columns {
+        Some(c) => c.split(',').collect(),
+        None => {
+            let mut names = key.to_vec();
+            expr.field_names(&mut names);
+            names
+        }
+    };
+    let n = query::run(records, &expr, &columns, json, std::io::stdout().lock())?;
+    eprintln!("{} matches", n);
+    Ok(())
+}
+
+/// mids-sync query [--json] [--columns=a,b,...] I12.mhd power|effect|powerset|archetype|summon EXPR
+fn query_command(mut args: Vec<OsString>) -> Result<()> {
+    let json = take_flag(&mut args, "--json");
+    let columns = take_option(&mut args, "--columns")?;
+    let columns = columns.as_deref();
+    let [input, target, expr] = args.as_slice() else {
+        bail!("usage: mids-sync query [--json] [--columns=a,b,...] I12.mhd power|effect|powerset|archetype|summon EXPR");
+    };
+    let mdb = load_mids(Path::new(input))?;
+    let expr = expr.to_str().context("query isn't valid UTF-8")?;
+    match target.to_str() {
+        Some("power") => run_query(mdb.powers.iter(), &["full_name"], expr, columns, json),
+        Some("effect") => run_query(
+            mdb.powers.iter().flat_map(|p| &p.effects),
+            &["power_full_name", "unique_id"],
+            expr,
+            columns,
+            json,
+        ),
+        Some("powerset") => run_query(mdb.powersets.iter(), &["full_name"], expr, columns, json),
+        Some("archetype") => run_query(mdb.archetypes.iter(), &["class_name"], expr, columns, json),
+        Some("summon") => run_query(mdb.summons.iter(), &["uid"], expr, columns, json),
+        _ => bail!("unknown record type {:?}", target),
+    }
+}
+
+/// mids-sync patch I12.mhd PATCH.json
+fn patch_command(args: Vec<OsString>) -> Result<()> {
+    let [input, patch_path] = args.as_slice() else {
+        bail!("usage: mids-sync patch I12.mhd PATCH.json");
+    };
+    let mut mdb = load_mids(Path::new(input))?;
+    let changes = patch::apply(&mut mdb, &patch::load(Path::new(patch_path))?)?;
+    for c in &changes {
+        println!("{}", c);
+    }
+    if changes.is_empty() {
+        println!("No changes");
+        return Ok(());
+    }
+    let w = prompt_save("Save location", "Mids Reborn Database (*.mhd)")?;
+    mids::to_writer(w, &mdb)?;
+    Ok(())
+}
+
+/// mids-sync [--migrate-names] [--version=bump|date|keep|VERSION] [--issue-from-cod] [--patch=PATCH.json]
+///           [--overrides=OVERRIDES.json] [--tolerances=TOLERANCES.json] [--powerset-map=MAP.json]
+///           [--propose-renames=MIN_SCORE] [--force] [CoD zip or directory]
+fn sync(mut args: Vec<OsString>) -> Result<()> {
+    // Rename Mids powersets and powers to their CoD names rather than only using the mapping for lookups.
+    let migrate_names = take_flag(&mut args, "--migrate-names");
+    let version_policy: version::Policy = take_option(&mut args, "--version")?.as_deref().unwrap_or("bump").parse()?;
+    // Set issue/page_volume/page_volume_name from the CoD revision.
+    let issue_from_cod = take_flag(&mut args, "--issue-from-cod");
+    // Hand fixes to replay after syncing.
+    let patch_rules = match take_option(&mut args, "--patch")? {
+        Some(p) => patch::load(Path::new(&p))?,
+        None => Vec::new(),
+    };
+    // Also sync powers marked never_auto_update. Requirements aren't synced from CoD at all;
+    // --migrate-names renames them regardless of never_auto_update_requirements.
+    let force = take_flag(&mut args, "--force");
+    // Per-power exceptions to the sync.
+    let overrides = match take_option(&mut args, "--overrides")? {
+        Some(p) => overrides::Overrides::load(Path::new(&p))?,
+        None => overrides::Overrides::default(),
+    };
+    // How closely values must match CoD to be left alone.
+    let tolerances = match take_option(&mut args, "--tolerances")? {
+        Some(p) => tolerance::Tolerances::load(Path::new(&p))?,
+        None => tolerance::Tolerances::default(),
+    };
+    // More Mids to CoD powerset renames, on top of the built-in ones.
+    let extra_powerset_map = match take_option(&mut args, "--powerset-map")? {
+        Some(p) => rename::load_powerset_map(Path::new(&p))?,
+        None => BTreeMap::new(),
+    };
+    // Suggest powerset renames scoring at least this much.
+    let propose_renames: Option<f32> = match take_option(&mut args, "--propose-renames")? {
+        Some(s) => Some(s.parse().with_context(|| format!("--propose-renames={}", s))?),
+        None => None,
+    };
+
+    let mids_path = mids_path(None)?;
+    // The CoD data can be given on the command line as either a zip or an extracted directory.
+    let cod_path = match args.first() {
+        Some(p) => PathBuf::from(p),
+        None => prompt_path("Locate CoD raw data", "Zip File (*.zip)")?,
+    };
+
+    let eclasses = load_eclasses(&mids_path)?;
+    let mut eclasses_map = BTreeMap::new();
+    for (i, v) in eclasses.iter().enumerate() {
+        if let Some(v) = v {
+            eclasses_map.insert(v.as_str(), u32::try_from(i)?);
         }
     }
 
-    let mut mdb = mids::from_reader(BufReader::new(File::open(mids_path)?))?;
+    let mut mdb = load_mids(&mids_path)?;
     println!("Using Mids DB version {}", mdb.version);
-    let mut cdb = cod::Database { zip: ZipArchive::new(zipf)? };
-    println!("Using CoD revision {}", cdb.revision()?);
-    let cidx = cdb.index()?;
+    let mut cdb = cod::Database::open(cod_path)?;
+    let revision = cdb.revision()?;
+    println!("Using CoD revision {}", revision);
+    // Parsed now so that a bad revision fails before the sync rather than just before saving.
+    let cod_issue = if issue_from_cod {
+        Some(version::parse_revision(&revision)?)
+    } else {
+        None
+    };
+    let cidx = cdb.index_cached(std::env::temp_dir().join("mids-sync").join("cod-index.json"))?;
 
-    // This mapping should be applied to Powerset::full_name and the first two parts of Power::full_name.
-    let powerset_map = BTreeMap::from([
+    // Maps Mids powerset names to CoD ones. Used for lookups, and with --migrate-names also applied to
+    // Powerset::full_name and the first two parts of Power::full_name.
+    let mut powerset_map = BTreeMap::from([
         ("blaster_support.temporal_manipulation", "blaster_support.time_manipulation"),
         ("controller_buff.electrical_affinity", "controller_buff.shock_therapy"),
         ("corruptor_buff.electrical_affinity", "corruptor_buff.shock_therapy"),
@@ -105,60 +456,61 @@ fn main() -> Result<()> {
         ("mastermind_buff.electrical_affinity", "mastermind_buff.shock_therapy"),
     ]);
 
-    let known_bad_display_name = BTreeSet::from([
-        "pets.titan_weapons.defensive_sweep_fast",
-        "pets.titan_weapons.crushing_blow_fast",
-        "pets.titan_weapons.sweeping_strike_fast",
-        "pets.titan_weapons.shatter_armor_fast",
-        "pets.titan_weapons.arc_of_destruction_fast",
-        "pets.titan_weapons_tanker.defensive_sweep_fast",
-        "pets.titan_weapons_tanker.crushing_blow_fast",
-        "pets.titan_weapons_tanker.sweeping_strike_fast",
-        "pets.titan_weapons_tanker.shatter_armor_fast",
-        "pets.titan_weapons_tanker.arc_of_destruction_fast",
-        "villain_pets.titan_weapons_brute.arc_of_destruction_fast",
-        "villain_pets.titan_weapons_brute.crushing_blow_fast",
-        "villain_pets.titan_weapons_brute.defensive_sweep_fast",
-        "villain_pets.titan_weapons_brute.shatter_armor_fast",
-        "villain_pets.titan_weapons_brute.sweeping_strike_fast",
-    ]);
+    powerset_map.extend(extra_powerset_map.iter().map(|(m, c)| (m.as_str(), c.as_str())));
 
-    let boost_map = BTreeMap::from_iter(
-        [
-            ("Enhance Accuracy", "Accuracy_Boost"),
-            ("Enhance Confuse", "Confuse_Boost"),
-            ("Enhance Damage", "Damage_Boost"),
-            ("Enhance Damage Resistance", "Res_Damage_Boost"),
-            ("Enhance Defense", "Buff_Defense_Boost"),
-            ("Enhance Defense DeBuff", "Debuff_Defense_Boost"),
-            ("Enhance Disorient", "Stunned_Boost"),
-            ("Enhance Endurance Modification", "Recovery_Boost"),
-            ("Enhance Fear", "Fear_Boost"),
-            ("Enhance Flying Speed", "SpeedFlying_Boost"),
-            ("Enhance Heal", "Heal_Boost"),
-            ("Enhance Hold", "Hold_Boost"),
-            ("Enhance Immobilization", "Immobilized_Boost"),
-            ("Enhance Intangibility", "Intangible_Boost"),
-            ("Enhance Jump", "Jump_Boost"),
-            ("Enhance KnockBack", "Knockback_Boost"),
-            ("Enhance Range", "Range_Boost"),
-            ("Enhance Recharge Speed", "Recharge_Boost"),
-            ("Enhance Running Speed", "SpeedRunning_Boost"),
-            ("Enhance Sleep", "Sleep_Boost"),
-            ("Enhance Slow Movement", "Slow_Boost"),
-            ("Enhance Threat Duration", "Taunt_Boost"),
-            ("Enhance ToHit Buffs", "Buff_ToHit_Boost"),
-            ("Enhance ToHit DeBuffs", "Debuff_ToHit_Boost"),
-            ("Incarnate: Destiny Capable", "Incarnate_Destiny_Boost"),
-            ("Incarnate: Interface Capable", "Incarnate_Interface_Boost"),
-            ("Incarnate: Judgement Capable", "Incarnate_Judgement_Boost"),
-            ("Incarnate: Lore Capable", "Incarnate_Lore_Boost"),
-            ("Reduce Endurance Cost", "EnduranceDiscount_Boost"),
-            ("Reduce Interrupt Time", "Interrupt_Boost"),
-        ]
-        .map(|(k, v)| (k, *eclasses_map.get(v).unwrap())),
-    );
+    // Suggest additions to powerset_map for sets that were renamed on one side.
+    if let Some(min_score) = propose_renames {
+        let proposals = rename::propose_powerset_renames(&mdb, &cidx, &powerset_map, min_score);
+        for r in &proposals {
+            println!("Possible powerset rename (score {:.2}): {} -> {}", r.score, r.mids_set, r.cod_set);
+        }
+        if !proposals.is_empty() {
+            let entries: BTreeMap<&str, &str> = proposals.iter().map(|r| (r.mids_set.as_str(), r.cod_set.as_str())).collect();
+            println!("As --powerset-map entries:\n{}", serde_json::to_string_pretty(&entries)?);
+        }
+    }
 
+    let mut changed = false;
+    if migrate_names {
+        let (report, n) = migrate::rename_powersets(&mut mdb, &powerset_map);
+        for r in report {
+            println!("{}", r);
+        }
+        println!("Migrated {} powerset and power names", n);
+        changed |= n > 0;
+    }
+    overrides.warn_unmatched(&mdb);
+
+    // Load the CoD entities behind summons, both those Mids has and those EntCreate effects want.
+    // Pet powers are resolved through them rather than by display name.
+    let mut entity_names: Vec<String> = mdb
+        .summons
+        .iter()
+        .map(|s| s.uid.as_str())
+        .chain(summons::references(&mdb).into_iter().map(|(uid, _)| uid))
+        .map(str::to_ascii_lowercase)
+        .collect();
+    entity_names.sort_unstable();
+    entity_names.dedup();
+    let entities = cdb.load_entities(&entity_names.iter().map(String::as_str).collect::<Vec<_>>())?;
+    let (pet_powers, report) = summons::pet_powers(&mdb, &entities, &powerset_map, &cidx);
+    for r in report {
+        println!("{}", r);
+    }
+
+    // Map CoD boost display names to Mids enhancement classes.
+    let mut boost_map = BTreeMap::new();
+    for b in cdb.load_boosts()? {
+        match eclasses_map.get(b.name.as_str()) {
+            Some(&e) => {
+                boost_map.insert(b.display_name, e);
+            }
+            None => println!("CoD boost {} ({}) has no matching entry in EClasses.mhd", b.name, b.display_name),
+        }
+    }
+
+    // Maps CoD boostset categories to Mids set types. This has to be kept by hand: Mids has no
+    // display names for its set types to match against.
     let boostset_map = BTreeMap::from([
         ("Accurate Defense Debuff", mids::enums::SetType::AccDefDeb),
         ("Accurate Healing", mids::enums::SetType::AccHeal),
@@ -208,43 +560,142 @@ fn main() -> Result<()> {
         ("Universal Damage Sets", mids::enums::SetType::UniversalDamage),
         ("Universal Travel", mids::enums::SetType::Travel),
     ]);
+    let boost_sets = cdb.load_boost_sets()?;
+    let unmapped_cats: BTreeSet<&str> = boost_sets
+        .iter()
+        .map(|s| s.group_name.as_str())
+        .filter(|c| !boostset_map.contains_key(c))
+        .collect();
+    if !unmapped_cats.is_empty() {
+        // Powers allowing these are reported below and keep their Mids set types.
+        println!("CoD boostset categories with no Mids set type: {:?}", unmapped_cats);
+    }
 
-    let mut changed = false;
-    for p in &mut mdb.powers {
-        // If there's an AttribMod, skip this.
-        if p.effects.iter().any(|e| e.power_attribs != mids::enums::PowerAttribs::None) {
-            continue;
-        }
+    let mut unmapped_boosts = BTreeSet::new();
+
+    // Work out which CoD powers each Mids power could correspond to, then load them all up front.
+    let mut cod_names = Vec::with_capacity(mdb.powers.len());
+    for (i, p) in mdb.powers.iter().enumerate() {
         let nl: String = p.full_name.to_ascii_lowercase();
-        let mut cod_full_name = &p.full_name;
-        if !known_bad_display_name.contains(nl.as_str()) {
+        let mut candidates = Vec::new();
+        if let Some(cod_power) = overrides.get(&p.full_name).and_then(|r| r.cod_power.as_ref()) {
+            println!("{}: using CoD power {} by override", p.full_name, cod_power);
+            candidates.push(cod_power.clone());
+        } else if let Some(cod_power) = pet_powers.get(&i) {
+            // Pet powers the entity chain couldn't resolve fall back to the Mids full name below, as
+            // their display names aren't reliable (e.g. pets.titan_weapons.*_fast).
+            candidates.extend(cod_power.clone());
+        } else {
             // Look up the display name instead of relying on full name.
             let (mut sn, _) = nl.rsplit_once('.').unwrap();
             if let Some(&repl) = powerset_map.get(sn) {
                 sn = repl;
             }
-            if let Some(sidx) = cidx.get(sn) {
-                if let Some(n) = sidx.get(&p.display_name) {
-                    cod_full_name = n;
-                }
+            if let Some(names) = cidx.get(sn).and_then(|sidx| sidx.get(&p.display_name)) {
+                candidates.clone_from(names);
             }
         }
-        let cod_p = match cdb.load_power(cod_full_name) {
-            Ok(p) => p,
-            Err(e) => {
-                if let Some(ZipError::FileNotFound) = e.downcast_ref::<ZipError>() {
-                    continue;
+        if candidates.len() > 1 {
+            // Several powers share the display name; prefer one with the same short name.
+            let short = p.power_name.to_ascii_lowercase();
+            if let Some(n) = candidates.iter().find(|n| n.rsplit('.').next() == Some(short.as_str())) {
+                candidates = vec![n.clone()];
+            }
+        }
+        if candidates.is_empty() {
+            candidates.push(p.full_name.clone());
+        }
+        cod_names.push(candidates);
+    }
+    let mut preload: Vec<&str> = cod_names.iter().flatten().map(String::as_str).collect();
+    // Ambiguous powers may fall back to the Mids full name.
+    preload.extend(
+        mdb.powers
+            .iter()
+            .zip(&cod_names)
+            .filter(|(_, c)| c.len() > 1)
+            .map(|(p, _)| p.full_name.as_str()),
+    );
+    // The Incarnate pass needs every power in each slot.
+    preload.extend(incarnate::cod_names(&mdb, &cidx, &powerset_map));
+    preload.sort_unstable();
+    preload.dedup();
+    let mut cod_powers = cdb.load_powers(&preload)?;
+    // Also load the targets of CoD redirects, for their timing.
+    let mut redirects: Vec<String> = cod_powers
+        .values()
+        .flat_map(|p| &p.redirect)
+        .filter(|r| !cod_powers.contains_key(&r.name))
+        .map(|r| r.name.clone())
+        .collect();
+    redirects.sort_unstable();
+    redirects.dedup();
+    let redirect_powers = cdb.load_powers(&redirects.iter().map(String::as_str).collect::<Vec<_>>())?;
+    cod_powers.extend(redirect_powers);
+
+    let mut ambiguous = Vec::new();
+    let mut locked = Vec::new();
+    let mut near_misses = Vec::new();
+    // The CoD power each Mids power was matched with.
+    let mut resolved = vec![None; mdb.powers.len()];
+    for ((p, candidates), resolved) in mdb.powers.iter_mut().zip(&cod_names).zip(&mut resolved) {
+        // Locked powers are still matched up, so that the later passes can report them.
+        let is_locked = p.never_auto_update && !force;
+        if is_locked {
+            locked.push(p.full_name.clone());
+        }
+        let cod_full_name = match candidates.as_slice() {
+            [] => continue,
+            [n] => n,
+            _ => {
+                // Still ambiguous, so try to narrow it down by level.
+                let by_level: Vec<&String> = candidates
+                    .iter()
+                    .filter(|n| cod_powers.get(*n).is_some_and(|c| c.available_level + 1 == p.level))
+                    .collect();
+                if let [n] = by_level.as_slice() {
+                    n
+                } else {
+                    ambiguous.push((p.full_name.clone(), p.display_name.clone(), candidates));
+                    &p.full_name
                 }
-                return Err(e);
             }
         };
+        let Some(cod_p) = cod_powers.get(cod_full_name) else {
+            continue;
+        };
+        *resolved = Some(cod_full_name.clone());
+        if is_locked {
+            continue;
+        }
+        let rule = overrides.get(&p.full_name);
+        if let Some(r) = rule.filter(|r| !r.comment.is_empty()) {
+            println!("{}: override applies: {}", p.full_name, r.comment);
+        }
+        let skip = |f| rule.is_some_and(|r| r.skips(f));
+        if skip(overrides::SyncField::All) {
+            println!("{}: not synced by override", p.full_name);
+            continue;
+        }
 
         if p.group_name != "Boosts" && p.group_name != "Incarnate" {
             // Fix eligible enhancement sets.
             let mids_enhs = BTreeSet::from_iter(p.enhancements.iter().copied());
-            let cod_enhs = BTreeSet::from_iter(cod_p.boosts_allowed.iter().filter_map(|b| boost_map.get(b.as_str()).copied()));
+            let mut cod_enhs = BTreeSet::new();
+            for b in &cod_p.boosts_allowed {
+                match boost_map.get(b.as_str()) {
+                    Some(&e) => {
+                        cod_enhs.insert(e);
+                    }
+                    None => {
+                        unmapped_boosts.insert(b.clone());
+                    }
+                }
+            }
 
-            if mids_enhs != cod_enhs {
+            if mids_enhs != cod_enhs && skip(overrides::SyncField::Boosts) {
+                println!("{} ({}): boosts differ from CoD but override skips them", p.full_name, p.display_name);
+            } else if mids_enhs != cod_enhs {
                 let missing: Vec<&str> = cod_enhs
                     .difference(&mids_enhs)
                     .map(|&e| eclasses[e as usize].as_ref().unwrap().as_str())
@@ -272,7 +723,19 @@ fn main() -> Result<()> {
             }
 
             let mids_sets = BTreeSet::from_iter(p.set_types.iter().copied());
-            let mut cod_sets = BTreeSet::from_iter(cod_p.allowed_boostset_cats.iter().map(|b| *boostset_map.get(b.as_str()).unwrap()));
+            let mut cod_sets = BTreeSet::new();
+            let mut sets_ok = true;
+            for b in &cod_p.allowed_boostset_cats {
+                match boostset_map.get(b.as_str()) {
+                    Some(&t) => {
+                        cod_sets.insert(t);
+                    }
+                    None => {
+                        println!("{} ({}): unknown boostset category {:?}, not fixing sets", p.full_name, p.display_name, b);
+                        sets_ok = false;
+                    }
+                }
+            }
             // There is no Flight/Teleport & Sprints category, because Sprint doesn't fly or teleport, but Mids did a dumb.
             if cod_sets.contains(&mids::enums::SetType::Flight) {
                 cod_sets.insert(mids::enums::SetType::FlightNoSprint);
@@ -280,7 +743,19 @@ fn main() -> Result<()> {
             if cod_sets.contains(&mids::enums::SetType::Teleport) {
                 cod_sets.insert(mids::enums::SetType::TeleportNoSprint);
             }
-            if mids_sets != cod_sets {
+            // Ignored set types stay as Mids has them.
+            for t in overrides.ignored_set_types(&p.full_name) {
+                if cod_sets.contains(t) != mids_sets.contains(t) {
+                    println!("{} ({}): set type {:?} left alone by override", p.full_name, p.display_name, t);
+                    cod_sets.remove(t);
+                    if mids_sets.contains(t) {
+                        cod_sets.insert(*t);
+                    }
+                }
+            }
+            if sets_ok && mids_sets != cod_sets && skip(overrides::SyncField::SetTypes) {
+                println!("{} ({}): sets differ from CoD but override skips them", p.full_name, p.display_name);
+            } else if sets_ok && mids_sets != cod_sets {
                 let missing: Vec<mids::enums::SetType> = cod_sets.difference(&mids_sets).copied().collect();
                 let extra: Vec<mids::enums::SetType> = mids_sets.difference(&cod_sets).copied().collect();
                 if !missing.is_empty() {
@@ -302,14 +777,23 @@ fn main() -> Result<()> {
         }
 
         // Fix level at which the power becomes available.
-        let cod_level = if p.group_name == "Pool" && cod_p.available_level == 0 {
+        let cod_level = if let Some(level) = rule.and_then(|r| r.level) {
+            if level != cod_p.available_level + 1 {
+                println!("{}: level forced to {} by override", p.full_name, level);
+            }
+            level
+        } else if p.group_name == "Incarnate" {
+            p.level // Set per slot by the Incarnate pass.
+        } else if p.group_name == "Pool" && cod_p.available_level == 0 {
             4 // Pools aren't available before level 4.
         } else if cod_p.available_level == 0 && cod_p.power_lifetime != 0.0 {
             0 // Temporary powers shouldn't have levels. (In particular, Seismic Shockwaves.)
         } else {
             cod_p.available_level + 1
         };
-        if p.level != cod_level {
+        if p.level != cod_level && skip(overrides::SyncField::Level) {
+            println!("{}: level {} differs from CoD {} but override skips it", p.full_name, p.level, cod_level);
+        } else if p.level != cod_level {
             println!("{}: level {} fixed to {} [from {}]", p.full_name, p.level, cod_level, cod_p.full_name);
             p.level = cod_level;
             changed = true;
@@ -325,38 +809,257 @@ fn main() -> Result<()> {
         */
 
         // Powers that don't end in an "Always" don't always have sensible values. If Mids isn't
-        // using redirects for this power, skip syncing certain problematic attributes (looking at
-        // you, Time Bomb).
+        // using redirects for this power, take the timing from the CoD redirect targets instead,
+        // provided they agree (looking at you, Time Bomb).
         let mids_has_redirect = p.effects.iter().any(|e| e.effect_type == mids::enums::EffectType::PowerRedirect);
-        let cod_safe_redirect = !cod_p.redirect.last().is_some_and(|r| r.condition_expression != "Always");
-        if mids_has_redirect || cod_safe_redirect {
+        let cod_safe_redirect = cod_p.redirect.last().is_none_or(|r| r.condition_expression == "Always");
+        let timing = if mids_has_redirect || cod_safe_redirect {
+            Some(cod_p)
+        } else {
+            let targets: Vec<&cod::Power> = cod_p.redirect.iter().filter_map(|r| cod_powers.get(&r.name)).collect();
+            match targets.as_slice() {
+                [first, rest @ ..]
+                    if targets.len() == cod_p.redirect.len()
+                        && rest
+                            .iter()
+                            .all(|t| t.recharge_time == first.recharge_time && t.activation_time == first.activation_time) =>
+                {
+                    Some(*first)
+                }
+                _ => {
+                    println!(
+                        "{} ({}): CoD redirect targets disagree on timing, not syncing it",
+                        p.full_name, p.display_name
+                    );
+                    None
+                }
+            }
+        };
+        if let Some(timing) = timing {
             // Fix recharge time.
-            if p.recharge_time != cod_p.recharge_time {
+            let tol = &tolerances.recharge_time;
+            match tol.compare(p.recharge_time, timing.recharge_time) {
+                Match::Exact => {}
+                Match::Near => near_misses.push(format!(
+                    "{} ({}): recharge_time {} vs CoD {}",
+                    p.full_name, p.display_name, p.recharge_time, timing.recharge_time
+                )),
+                Match::Differs if skip(overrides::SyncField::RechargeTime) => println!(
+                    "{} ({}): recharge_time {} differs from CoD {} but override skips it",
+                    p.full_name, p.display_name, p.recharge_time, timing.recharge_time
+                ),
+                Match::Differs => {
+                    let expected = tol.expected(timing.recharge_time);
+                    println!(
+                        "{} ({}): recharge_time {} fixed to {} [from {}]",
+                        p.full_name, p.display_name, p.recharge_time, expected, timing.full_name
+                    );
+                    p.recharge_time = expected;
+                    p.base_recharge_time = p.recharge_time;
+                    changed = true;
+                }
+            }
+            // Fix cast time.
+            let tol = &tolerances.cast_time;
+            match tol.compare(p.cast_time, timing.activation_time) {
+                Match::Exact => {}
+                Match::Near => near_misses.push(format!(
+                    "{} ({}): cast_time {} vs CoD {}",
+                    p.full_name, p.display_name, p.cast_time, timing.activation_time
+                )),
+                Match::Differs if skip(overrides::SyncField::CastTime) => println!(
+                    "{} ({}): cast_time {} differs from CoD {} but override skips it",
+                    p.full_name, p.display_name, p.cast_time, timing.activation_time
+                ),
+                Match::Differs => {
+                    let expected = tol.expected(timing.activation_time);
+                    println!(
+                        "{} ({}): cast_time {} fixed to {} [from {}]",
+                        p.full_name, p.display_name, p.cast_time, expected, timing.full_name
+                    );
+                    p.cast_time = expected;
+                    changed = true;
+                }
+            }
+        }
+
+        // Reconcile AttribMod effects with the CoD base values and attrib mods.
+        if p.effects.iter().any(|e| e.power_attribs != mids::enums::PowerAttribs::None) {
+            let (report, fixed) = attribmod::sync(
+                p,
+                cod_p,
+                !skip(overrides::SyncField::RechargeTime),
+                !skip(overrides::SyncField::CastTime),
+                &tolerances,
+                &mut near_misses,
+            );
+            for r in report {
+                println!("{}", r);
+            }
+            changed |= fixed;
+        }
+    }
+
+    // Match Mids redirect effects up with the CoD redirect chain, and sync the timing of each
+    // redirect target from its CoD counterpart.
+    let to_cod_name = |name: &str| -> String {
+        let nl = name.to_ascii_lowercase();
+        match nl.rsplit_once('.') {
+            Some((set, power)) => match powerset_map.get(set) {
+                Some(repl) => format!("{}.{}", repl, power),
+                None => nl,
+            },
+            None => nl,
+        }
+    };
+    let midx = mdb.indices();
+    let mut redirect_fixes = Vec::new();
+    for (p, cod_name) in mdb.powers.iter().zip(&resolved) {
+        let Some(cod_p) = cod_name.as_ref().and_then(|n| cod_powers.get(n)) else {
+            continue;
+        };
+        if overrides.get(&p.full_name).is_some_and(|r| r.skips(overrides::SyncField::All)) {
+            continue;
+        }
+        let mids_targets: Vec<&str> = p
+            .effects
+            .iter()
+            .filter(|e| e.effect_type == mids::enums::EffectType::PowerRedirect)
+            .map(mids::Effect::redirect_target)
+            .collect();
+        if mids_targets.is_empty() {
+            continue;
+        }
+        for r in &cod_p.redirect {
+            let Some(&target) = mids_targets.iter().find(|t| to_cod_name(t).eq_ignore_ascii_case(&r.name)) else {
                 println!(
-                    "{} ({}): recharge_time {} fixed to {} [from {}]",
-                    p.full_name, p.display_name, p.recharge_time, cod_p.recharge_time, cod_p.full_name
+                    "{}: no Mids redirect for CoD redirect to {} ({})",
+                    p.full_name, r.name, r.condition_expression
                 );
-                p.recharge_time = cod_p.recharge_time;
-                p.base_recharge_time = p.recharge_time;
+                continue;
+            };
+            if let (Some(i), Some(cod_t)) = (midx.power(target), cod_powers.get(&r.name)) {
+                redirect_fixes.push((i, cod_t));
+            }
+        }
+        for t in &mids_targets {
+            if !cod_p.redirect.iter().any(|r| to_cod_name(t).eq_ignore_ascii_case(&r.name)) {
+                println!("{}: Mids redirect to {} has no CoD counterpart", p.full_name, t);
+            }
+        }
+    }
+    for (i, cod_t) in redirect_fixes {
+        let t = &mut mdb.powers[i];
+        if t.never_auto_update && !force {
+            continue;
+        }
+        let rule = overrides.get(&t.full_name);
+        let skip = |f| rule.is_some_and(|r| r.skips(f));
+        match tolerances.recharge_time.compare(t.recharge_time, cod_t.recharge_time) {
+            Match::Exact => {}
+            Match::Near => near_misses.push(format!(
+                "{} ({}): recharge_time {} vs CoD redirect {} {}",
+                t.full_name, t.display_name, t.recharge_time, cod_t.full_name, cod_t.recharge_time
+            )),
+            Match::Differs if skip(overrides::SyncField::RechargeTime) => println!(
+                "{} ({}): recharge_time {} differs from CoD redirect {} {} but override skips it",
+                t.full_name, t.display_name, t.recharge_time, cod_t.full_name, cod_t.recharge_time
+            ),
+            Match::Differs => {
+                let expected = tolerances.recharge_time.expected(cod_t.recharge_time);
+                println!(
+                    "{} ({}): recharge_time {} fixed to {} [from redirect {}]",
+                    t.full_name, t.display_name, t.recharge_time, expected, cod_t.full_name
+                );
+                t.recharge_time = expected;
+                t.base_recharge_time = t.recharge_time;
                 changed = true;
             }
-            // Fix cast time.
-            if p.cast_time != cod_p.activation_time {
+        }
+        match tolerances.cast_time.compare(t.cast_time, cod_t.activation_time) {
+            Match::Exact => {}
+            Match::Near => near_misses.push(format!(
+                "{} ({}): cast_time {} vs CoD redirect {} {}",
+                t.full_name, t.display_name, t.cast_time, cod_t.full_name, cod_t.activation_time
+            )),
+            Match::Differs if skip(overrides::SyncField::CastTime) => println!(
+                "{} ({}): cast_time {} differs from CoD redirect {} {} but override skips it",
+                t.full_name, t.display_name, t.cast_time, cod_t.full_name, cod_t.activation_time
+            ),
+            Match::Differs => {
+                let expected = tolerances.cast_time.expected(cod_t.activation_time);
                 println!(
-                    "{} ({}): cast_time {} fixed to {} [from {}]",
-                    p.full_name, p.display_name, p.cast_time, cod_p.activation_time, cod_p.full_name
+                    "{} ({}): cast_time {} fixed to {} [from redirect {}]",
+                    t.full_name, t.display_name, t.cast_time, expected, cod_t.full_name
                 );
-                p.cast_time = cod_p.activation_time;
+                t.cast_time = expected;
                 changed = true;
             }
         }
     }
 
+    // Incarnate and Boosts powers follow their own rules.
+    let (report, fixed) = incarnate::sync(&mut mdb, &resolved, &cod_powers, &cidx, &powerset_map, &overrides, force);
+    for r in report {
+        println!("{}", r);
+    }
+    changed |= fixed;
+    let (report, fixed) = boosts::sync(&mut mdb, &resolved, &cod_powers, &tolerances.scale, &mut near_misses, &overrides, force);
+    for r in report {
+        println!("{}", r);
+    }
+    changed |= fixed;
+    for r in boosts::check_sets(&mdb, &boost_sets) {
+        println!("{}", r);
+    }
+
+    // Sync summons from their CoD entities.
+    let (report, fixed) = summons::sync(&mut mdb, &entities, &powerset_map);
+    for r in report {
+        println!("{}", r);
+    }
+    changed |= fixed;
+
+    for c in patch::apply(&mut mdb, &patch_rules)? {
+        println!("{}", c);
+        changed = true;
+    }
+
+    if !locked.is_empty() {
+        println!(
+            "Skipped {} powers marked never_auto_update (use --force to sync them): {:?}",
+            locked.len(),
+            locked
+        );
+    }
+    for (full_name, display_name, candidates) in &ambiguous {
+        println!(
+            "{} ({}): ambiguous CoD match among {:?}, used Mids full name",
+            full_name, display_name, candidates
+        );
+    }
+    if !near_misses.is_empty() {
+        println!("Within tolerance of CoD, left alone:");
+        for n in &near_misses {
+            println!("    {}", n);
+        }
+    }
+    if !unmapped_boosts.is_empty() {
+        println!("CoD boosts with no Mids enhancement class (ignored): {:?}", unmapped_boosts);
+    }
+
     if changed {
-        let (h, t) = mdb.version.rsplit_once('.').unwrap();
-        let build: usize = t.parse().unwrap();
-        mdb.version = format!("{}.{}", h, build + 1);
-        println!("Updated DB version to {}", mdb.version);
+        // Edits such as --migrate-names can leave numeric ids stale.
+        for c in mdb.rebuild_nids() {
+            println!("{}", c);
+        }
+
+        if let Some(r) = &cod_issue {
+            version::apply_revision(&mut mdb, r);
+            println!("Updated DB issue to {} page {} ({})", mdb.issue, mdb.page_volume, mdb.page_volume_name);
+        }
+        version::apply(&mut mdb, &version_policy, SystemTime::now())?;
+        println!("Using DB version {}", mdb.version);
 
         let w = prompt_save("Save location", "Mids Reborn Database (*.mhd)")?;
         mids::to_writer(w, &mdb)?;
@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::mids::{self, fields::Fields};
+
+/// A change made differently on both sides. The merged database keeps ours.
+#[derive(Debug, Serialize)]
+pub struct Conflict {
+    pub path: String,
+    pub base: Option<Value>,
+    pub ours: Option<Value>,
+    pub theirs: Option<Value>,
+}
+
+/// Fields holding lists of records that are merged per record rather than as a whole, with the
+/// field that identifies each record.
+pub const KEYED_LISTS: &[(&str, &str)] = &[
+    ("archetypes", "class_name"),
+    ("powersets", "full_name"),
+    ("powers", "full_name"),
+    ("effects", "unique_id"),
+    ("summons", "uid"),
+];
+
+pub fn key_of(v: &Value, field: &str) -> String {
+    match &v[field] {
+        Value::String(s) => s.to_ascii_lowercase(),
+        other => other.to_string(),
+    }
+}
+
+/// Shows the record field at `path`, as found in conflicts and diff changes, the way mids::fields
+/// does. None if `path` isn't a field of a record in `mdb`.
+pub fn field_text(mdb: &mids::Database, path: &str) -> Option<String> {
+    fn get<T: Fields>(record: Option<&T>, field: &str) -> Option<String> {
+        T::field(field).zip(record).map(|(f, r)| (f.get)(r))
+    }
+    let (record, field) = path.rsplit_once("].")?;
+    if let Some((power, id)) = record.split_once("].effects[") {
+        let power = power.strip_prefix("powers[")?;
+        let p = mdb.powers.iter().find(|p| p.full_name.eq_ignore_ascii_case(power))?;
+        return get(p.effects.iter().find(|e| e.unique_id.to_string() == id), field);
+    }
+    let (list, key) = record.split_once('[')?;
+    match list {
+        "archetypes" => get(mdb.archetypes.iter().find(|a| a.class_name.eq_ignore_ascii_case(key)), field),
+        "powersets" => get(mdb.powersets.iter().find(|s| s.full_name.eq_ignore_ascii_case(key)), field),
+        "powers" => get(mdb.powers.iter().find(|p| p.full_name.eq_ignore_ascii_case(key)), field),
+        "summons" => get(mdb.summons.iter().find(|s| s.uid.eq_ignore_ascii_case(key)), field),
+        _ => None,
+    }
+}
+
+/// Replaces a value taken from `mdb` at `path` with its field_text, if it has one.
+pub fn show_field(mdb: &mids::Database, path: &str, value: &mut Option<Value>) {
+    if let (Some(v), Some(text)) = (value.as_mut(), field_text(mdb, path)) {
+        *v = Value::String(text);
+    }
+}
+
+struct Merger {
+    conflicts: Vec<Conflict>,
+}
+
+impl Merger {
+    /// Three-way merge of a single value, where None means absent.
+    fn value(&mut self, path: &str, base: Option<&Value>, ours: Option<&Value>, theirs: Option<&Value>) -> Option<Value> {
+        if ours == theirs {
+            return ours.cloned();
+        }
+        if ours == base {
+            return theirs.cloned();
+        }
+        if theirs == base {
+            return ours.cloned();
+        }
+        if let (Some(Value::Object(o)), Some(Value::Object(t))) = (ours, theirs) {
+            let b = base.and_then(Value::as_object);
+            return Some(Value::Object(self.object(path, b, o, t)));
+        }
+        self.conflicts.push(Conflict {
+            path: path.to_string(),
+            base: base.cloned(),
+            ours: ours.cloned(),
+            theirs: theirs.cloned(),
+        });
+        ours.cloned()
+    }
+
+    fn object(
+        &mut self,
+        path: &str,
+        base: Option<&Map<String, Value>>,
+        ours: &Map<String, Value>,
+        theirs: &Map<String, Value>,
+    ) -> Map<String, Value> {
+        let mut merged = Map::new();
+        for (k, o) in ours {
+            let b = base.and_then(|b| b.get(k));
+            let t = theirs.get(k);
+            let field_path = if path.is_empty() { k.clone() } else { format!("{}.{}", path, k) };
+            let keyed = KEYED_LISTS.iter().find(|(name, _)| name == k);
+            let v = match (keyed, b, o, t) {
+                (Some((_, key)), b, Value::Array(o), Some(Value::Array(t))) => {
+                    let b = b.and_then(Value::as_array).map_or(&[][..], Vec::as_slice);
+                    Some(Value::Array(self.list(&field_path, key, b, o, t)))
+                }
+                _ => self.value(&field_path, b, Some(o), t),
+            };
+            if let Some(v) = v {
+                merged.insert(k.clone(), v);
+            }
+        }
+        merged
+    }
+
+    /// Merges lists of records matched by `key`. The result follows our order, with records added
+    /// only on their side appended in their order.
+    fn list<'a>(&mut self, path: &str, key: &str, base: &'a [Value], ours: &'a [Value], theirs: &'a [Value]) -> Vec<Value> {
+        let by_key = |list: &'a [Value]| -> HashMap<String, &'a Value> { list.iter().map(|v| (key_of(v, key), v)).collect() };
+        let (b, o, t) = (by_key(base), by_key(ours), by_key(theirs));
+        let mut merged = Vec::with_capacity(ours.len());
+        for v in ours {
+            let k = key_of(v, key);
+            merged.extend(self.value(&format!("{}[{}]", path, k), b.get(&k).copied(), Some(v), t.get(&k).copied()));
+        }
+        for v in theirs {
+            let k = key_of(v, key);
+            if !o.contains_key(&k) {
+                merged.extend(self.value(&format!("{}[{}]", path, k), b.get(&k).copied(), None, Some(v)));
+            }
+        }
+        merged
+    }
+}
+
+/// Three-way merge of Mids databases, per record and per field. Records are matched by name (or
+/// unique_id for effects) rather than position. Returns the merged database and the conflicts,
+/// which are resolved in favour of ours. Conflicting field values are shown as text.
+pub fn merge(base: &mids::Database, ours: &mids::Database, theirs: &mids::Database) -> anyhow::Result<(mids::Database, Vec<Conflict>)> {
+    let to_object = |db: &mids::Database| -> anyhow::Result<Map<String, Value>> {
+        match serde_json::to_value(db)? {
+            Value::Object(m) => Ok(m),
+            _ => unreachable!(),
+        }
+    };
+    let (b, o, t) = (to_object(base)?, to_object(ours)?, to_object(theirs)?);
+    let mut m = Merger { conflicts: Vec::new() };
+    let merged = m.object("", Some(&b), &o, &t);
+    for c in &mut m.conflicts {
+        show_field(base, &c.path, &mut c.base);
+        show_field(ours, &c.path, &mut c.ours);
+        show_field(theirs, &c.path, &mut c.theirs);
+    }
+    Ok((serde_json::from_value(Value::Object(merged))?, m.conflicts))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn merge_values(base: Value, ours: Value, theirs: Value) -> (Value, Vec<Conflict>) {
+        let mut m = Merger { conflicts: Vec::new() };
+        let obj = |v: &Value| v.as_object().unwrap().clone();
+        let merged = m.object("", Some(&obj(&base)), &obj(&ours), &obj(&theirs));
+        (Value::Object(merged), m.conflicts)
+    }
+
+    fn power(name: &str, recharge: f64) -> Value {
+        json!({ "full_name": name, "recharge_time": recharge, "effects": [] })
+    }
+
+    #[test]
+    fn one_side() {
+        let base = json!({ "version": "1.0", "powers": [power("A", 10.0), power("B", 20.0)] });
+        let ours = json!({ "version": "1.0", "powers": [power("A", 10.0), power("B", 25.0)] });
+        let theirs = json!({ "version": "1.1", "powers": [power("A", 10.0), power("B", 20.0)] });
+        let (merged, conflicts) = merge_values(base, ours, theirs);
+        assert!(conflicts.is_empty());
+        assert_eq!(merged, json!({ "version": "1.1", "powers": [power("A", 10.0), power("B", 25.0)] }));
+    }
+
+    #[test]
+    fn both_sides() {
+        let base = json!({ "powers": [power("A", 10.0)] });
+        let ours = json!({ "powers": [power("A", 15.0)] });
+        let theirs = json!({ "powers": [power("A", 20.0)] });
+        let (merged, conflicts) = merge_values(base, ours, theirs);
+        assert_eq!(merged, json!({ "powers": [power("A", 15.0)] }));
+        assert_eq!(conflicts.len(), 1);
+        let c = &conflicts[0];
+        assert_eq!(c.path, "powers[a].recharge_time");
+        assert_eq!(
+            (c.base.clone(), c.ours.clone(), c.theirs.clone()),
+            (Some(json!(10.0)), Some(json!(15.0)), Some(json!(20.0)))
+        );
+    }
+
+    #[test]
+    fn added_by_theirs() {
+        let base = json!({ "powers": [power("A", 10.0)] });
+        let ours = json!({ "powers": [power("A", 10.0)] });
+        let theirs = json!({ "powers": [power("C", 30.0), power("A", 10.0)] });
+        let (merged, conflicts) = merge_values(base, ours, theirs);
+        assert!(conflicts.is_empty());
+        assert_eq!(merged, json!({ "powers": [power("A", 10.0), power("C", 30.0)] }));
+    }
+
+    #[test]
+    fn deleted_and_modified() {
+        let base = json!({ "powers": [power("A", 10.0), power("B", 20.0)] });
+        // Deleted by ours, modified by theirs: ours wins, so it stays deleted.
+        let (merged, conflicts) = merge_values(
+            base.clone(),
+            json!({ "powers": [power("A", 10.0)] }),
+            json!({ "powers": [power("A", 10.0), power("B", 25.0)] }),
+        );
+        assert_eq!(merged, json!({ "powers": [power("A", 10.0)] }));
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!((conflicts[0].path.as_str(), conflicts[0].ours.is_none()), ("powers[b]", true));
+
+        // Modified by ours, deleted by theirs.
+        let (merged, conflicts) = merge_values(
+            base.clone(),
+            json!({ "powers": [power("A", 10.0), power("B", 25.0)] }),
+            json!({ "powers": [power("A", 10.0)] }),
+        );
+        assert_eq!(merged, json!({ "powers": [power("A", 10.0), power("B", 25.0)] }));
+        assert_eq!(conflicts.len(), 1);
+        assert!(conflicts[0].theirs.is_none());
+
+        // Deleted by theirs and untouched by ours is a clean deletion.
+        let (merged, conflicts) = merge_values(
+            base,
+            json!({ "powers": [power("A", 10.0), power("B", 20.0)] }),
+            json!({ "powers": [power("A", 10.0)] }),
+        );
+        assert!(conflicts.is_empty());
+        assert_eq!(merged, json!({ "powers": [power("A", 10.0)] }));
+    }
+
+    #[test]
+    fn effects() {
+        let effect = |id: i64, scale: f64| json!({ "unique_id": id, "scale": scale });
+        let with = |recharge: f64, effects: Vec<Value>| {
+            let mut p = power("A", recharge);
+            p["effects"] = Value::Array(effects);
+            json!({ "powers": [p] })
+        };
+        let base = with(10.0, vec![effect(1, 1.0), effect(2, 2.0)]);
+        let ours = with(15.0, vec![effect(1, 1.5), effect(2, 2.0)]);
+        let theirs = with(10.0, vec![effect(1, 1.0), effect(2, 2.5), effect(3, 3.0)]);
+        let (merged, conflicts) = merge_values(base, ours, theirs);
+        assert!(conflicts.is_empty());
+        assert_eq!(merged, with(15.0, vec![effect(1, 1.5), effect(2, 2.5), effect(3, 3.0)]));
+    }
+}
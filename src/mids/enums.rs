@@ -1,15 +1,88 @@
-use std::{fmt::Debug, marker::PhantomData};
+use std::{fmt::Debug, marker::PhantomData, str::FromStr};
 
 use num_enum::{IntoPrimitive, TryFromPrimitive};
-use serde::{Deserialize, Serialize};
-use serde_repr::{Deserialize_repr, Serialize_repr};
+use serde::{
+    de::{self, Unexpected},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
 
-#[derive(Clone, Copy, Deserialize, Serialize)]
+use super::fields::Cell;
+
+#[derive(Clone, Copy)]
 pub struct BitVec<T> {
     value: u32,
     _marker: PhantomData<T>,
 }
 
+/// How BitVec is laid out in the database file.
+#[derive(Deserialize, Serialize)]
+#[serde(rename = "BitVec")]
+struct RawBitVec {
+    value: u32,
+    _marker: PhantomData<()>,
+}
+
+/// Human-readable formats get the list of flag names, with the bit number for any bit that has
+/// no name.
+impl<T: Debug + TryFrom<u32>> Serialize for BitVec<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if !serializer.is_human_readable() {
+            return RawBitVec {
+                value: self.value,
+                _marker: PhantomData,
+            }
+            .serialize(serializer);
+        }
+        let names = (0..u32::BITS)
+            .filter(|bit| self.value & (1 << bit) != 0)
+            .map(|bit| match T::try_from(bit) {
+                Ok(v) => format!("{:?}", v),
+                Err(_) => bit.to_string(),
+            });
+        serializer.collect_seq(names)
+    }
+}
+
+impl<'de, T: FromStr<Err = String> + Into<u32>> Deserialize<'de> for BitVec<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if !deserializer.is_human_readable() {
+            let raw = RawBitVec::deserialize(deserializer)?;
+            return Ok(BitVec {
+                value: raw.value,
+                _marker: PhantomData,
+            });
+        }
+        let mut value = 0;
+        for name in Vec::<String>::deserialize(deserializer)? {
+            let bit = match name.parse::<u32>() {
+                Ok(bit) if bit < u32::BITS => bit,
+                Ok(_) => return Err(de::Error::invalid_value(Unexpected::Str(&name), &"a flag name or bit number")),
+                Err(_) => name.parse::<T>().map_err(de::Error::custom)?.into(),
+            };
+            value |= 1 << bit;
+        }
+        Ok(BitVec { value, _marker: PhantomData })
+    }
+}
+
+/// Enums are stored as their u32 values, and shown by variant name in human-readable formats.
+fn serialize_variant<T: Debug + Copy + Into<u32>, S: Serializer>(v: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+        serializer.collect_str(&format_args!("{:?}", v))
+    } else {
+        serializer.serialize_u32((*v).into())
+    }
+}
+
+fn deserialize_variant<'de, T: FromStr<Err = String> + TryFrom<u32>, D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error> {
+    if deserializer.is_human_readable() {
+        String::deserialize(deserializer)?.parse().map_err(de::Error::custom)
+    } else {
+        let v = u32::deserialize(deserializer)?;
+        T::try_from(v).map_err(|_| de::Error::invalid_value(Unexpected::Unsigned(v.into()), &"a known variant"))
+    }
+}
+
 impl<T: Debug + TryFrom<u32>> Debug for BitVec<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut l = f.debug_list();
@@ -25,27 +98,93 @@ impl<T: Debug + TryFrom<u32>> Debug for BitVec<T> {
     }
 }
 
+impl<T: TryFrom<u32>> BitVec<T> {
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        (0..u32::BITS)
+            .filter(|bit| self.value & (1 << bit) != 0)
+            .filter_map(|bit| T::try_from(bit).ok())
+    }
+}
+
+impl<T: Into<u32>> FromIterator<T> for BitVec<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        BitVec {
+            value: iter.into_iter().fold(0, |v, t| v | 1 << t.into()),
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Parses variant names case-insensitively, matching the names shown by Debug.
+macro_rules! from_str_variants {
+    ($name:ident { $($v:ident),* }) => {
+        impl FromStr for $name {
+            type Err = String;
+
+            fn from_str(s: &str) -> Result<Self, String> {
+                $(
+                    if s.eq_ignore_ascii_case(stringify!($v)) {
+                        return Ok($name::$v);
+                    }
+                )*
+                Err(format!("unknown {} {:?}", stringify!($name), s))
+            }
+        }
+
+        impl Cell for $name {
+            fn to_cell(&self) -> String {
+                format!("{:?}", self)
+            }
+
+            fn from_cell(s: &str) -> Result<Self, String> {
+                s.parse()
+            }
+        }
+    };
+}
+
 macro_rules! cs_enum {
-        (Ord; $name:ident $tt:tt) => {
+        (Ord; $name:ident { $($v:ident $(= $n:literal)?),* $(,)? }) => {
             #[allow(non_camel_case_types)]
-            #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd, Deserialize_repr, Serialize_repr)]
+            #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd, TryFromPrimitive, IntoPrimitive)]
             #[repr(u32)]
-            pub enum $name $tt
+            pub enum $name { $($v $(= $n)?),* }
+            from_str_variants!($name { $($v),* });
+            serde_variants!($name);
         };
-        ($name:ident $tt:tt) => {
+        ($name:ident { $($v:ident $(= $n:literal)?),* $(,)? }) => {
             #[allow(non_camel_case_types)]
-            #[derive(Clone, Copy, Debug, PartialEq, Deserialize_repr, Serialize_repr)]
+            #[derive(Clone, Copy, Debug, PartialEq, TryFromPrimitive, IntoPrimitive)]
             #[repr(u32)]
-            pub enum $name $tt
+            pub enum $name { $($v $(= $n)?),* }
+            from_str_variants!($name { $($v),* });
+            serde_variants!($name);
         }
     }
 
+macro_rules! serde_variants {
+    ($name:ident) => {
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serialize_variant(self, serializer)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                deserialize_variant(deserializer)
+            }
+        }
+    };
+}
+
 macro_rules! bit_enum {
-        ($name:ident, $bits:ident $tt:tt) => {
+        ($name:ident, $bits:ident { $($v:ident $(= $n:literal)?),* $(,)? }) => {
             #[allow(non_camel_case_types)]
-            #[derive(Clone, Copy, Debug, TryFromPrimitive, IntoPrimitive)]
+            #[derive(Clone, Copy, Debug, PartialEq, TryFromPrimitive, IntoPrimitive)]
             #[repr(u32)]
-            pub enum $bits $tt
+            pub enum $bits { $($v $(= $n)?),* }
+            from_str_variants!($bits { $($v),* });
             pub type $name = BitVec<$bits>;
         };
     }
@@ -548,3 +687,52 @@ bit_enum!(
         Toxic,
     }
 );
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::netbinary;
+
+    #[derive(Debug, Deserialize, Serialize)]
+    struct Rec {
+        power_type: PowerType,
+        cast_flags: CastFlags,
+        #[serde(with = "netbinary::array_hack")]
+        kv: Vec<(String, String)>,
+    }
+
+    fn rec() -> Rec {
+        Rec {
+            power_type: PowerType::Toggle,
+            cast_flags: CastFlags::from_iter([CastKind::NearGround, CastKind::CastableAfterDeath]),
+            kv: vec![("a".to_string(), "b".to_string())],
+        }
+    }
+
+    #[test]
+    fn binary_layout() {
+        let mut s = netbinary::Serializer { writer: Vec::new() };
+        rec().serialize(&mut s).unwrap();
+        let bytes = s.writer;
+        assert_eq!(bytes, [2, 0, 0, 0, 0b101, 0, 0, 0, 1, 0, 0, 0, 1, b'a', 1, b'b']);
+        let r: Rec = netbinary::from_reader(&bytes[..]).unwrap();
+        assert_eq!(format!("{:?}", r), format!("{:?}", rec()));
+    }
+
+    #[test]
+    fn json_names() {
+        let json = serde_json::to_string(&rec()).unwrap();
+        assert_eq!(
+            json,
+            r#"{"power_type":"Toggle","cast_flags":["NearGround","CastableAfterDeath"],"kv":[["a","b"]]}"#
+        );
+        let r: Rec = serde_json::from_str(&json).unwrap();
+        assert_eq!(format!("{:?}", r), format!("{:?}", rec()));
+
+        let r: Rec = serde_json::from_str(r#"{"power_type":"toggle","cast_flags":["31"],"kv":[]}"#).unwrap();
+        assert_eq!(r.cast_flags.value, 1 << 31);
+        assert_eq!(serde_json::to_value(r.cast_flags).unwrap(), serde_json::json!(["31"]));
+        assert!(serde_json::from_str::<Rec>(r#"{"power_type":"Nope","cast_flags":[],"kv":[]}"#).is_err());
+        assert!(serde_json::from_str::<Rec>(r#"{"power_type":"Click","cast_flags":["Nope"],"kv":[]}"#).is_err());
+    }
+}
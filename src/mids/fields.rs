@@ -0,0 +1,309 @@
+//! Access to the simple fields of Mids records by name, as text. Used for tabular export, queries
+//! and patches.
+
+use std::{fmt::Debug, str::FromStr};
+
+use super::{enums::BitVec, Archetype, Effect, Power, Powerset, Summon};
+
+/// Separates the items of list-valued fields.
+pub const LIST_SEP: char = '|';
+
+/// A field value that can be shown as and parsed from text. Enums use their variant names.
+pub trait Cell: Sized {
+    /// Whether the text is a LIST_SEP-separated list.
+    const LIST: bool = false;
+
+    fn to_cell(&self) -> String;
+    fn from_cell(s: &str) -> Result<Self, String>;
+}
+
+macro_rules! display_cell {
+    ($($t:ty),*) => {
+        $(
+            impl Cell for $t {
+                fn to_cell(&self) -> String {
+                    self.to_string()
+                }
+
+                fn from_cell(s: &str) -> Result<Self, String> {
+                    s.trim().parse().map_err(|e| format!("{:?}: {}", s, e))
+                }
+            }
+        )*
+    };
+}
+
+display_cell!(bool, i32, u32, i64, f32);
+
+impl Cell for String {
+    fn to_cell(&self) -> String {
+        self.clone()
+    }
+
+    fn from_cell(s: &str) -> Result<Self, String> {
+        Ok(s.to_string())
+    }
+}
+
+impl<T: Cell> Cell for Vec<T> {
+    const LIST: bool = true;
+
+    fn to_cell(&self) -> String {
+        self.iter().map(Cell::to_cell).collect::<Vec<_>>().join(&LIST_SEP.to_string())
+    }
+
+    fn from_cell(s: &str) -> Result<Self, String> {
+        if s.is_empty() {
+            return Ok(Vec::new());
+        }
+        s.split(LIST_SEP).map(T::from_cell).collect()
+    }
+}
+
+impl<T: Debug + TryFrom<u32> + Into<u32> + FromStr<Err = String>> Cell for BitVec<T> {
+    const LIST: bool = true;
+
+    fn to_cell(&self) -> String {
+        self.iter().map(|v| format!("{:?}", v)).collect::<Vec<_>>().join(&LIST_SEP.to_string())
+    }
+
+    fn from_cell(s: &str) -> Result<Self, String> {
+        if s.is_empty() {
+            return Ok(BitVec::from_iter([]));
+        }
+        s.split(LIST_SEP).map(|v| v.trim().parse::<T>()).collect()
+    }
+}
+
+pub struct Field<T> {
+    pub name: &'static str,
+    pub list: bool,
+    pub get: fn(&T) -> String,
+    pub set: fn(&mut T, &str) -> Result<(), String>,
+}
+
+pub trait Fields: Sized + 'static {
+    const FIELDS: &'static [Field<Self>];
+
+    fn field(name: &str) -> Option<&'static Field<Self>> {
+        Self::FIELDS.iter().find(|f| f.name == name)
+    }
+}
+
+const fn list_of<T, C: Cell>(_: fn(&T) -> &C) -> bool {
+    C::LIST
+}
+
+macro_rules! fields {
+    ($ty:ident { $($f:ident),* $(,)? }) => {
+        impl Fields for $ty {
+            const FIELDS: &'static [Field<Self>] = &[$(
+                Field {
+                    name: stringify!($f),
+                    list: list_of(|v: &$ty| &v.$f),
+                    get: |v| v.$f.to_cell(),
+                    set: |v, s| {
+                        v.$f = Cell::from_cell(s)?;
+                        Ok(())
+                    },
+                },
+            )*];
+        }
+    };
+}
+
+fields!(Archetype {
+    display_name,
+    hitpoints,
+    hp_cap,
+    desc_long,
+    res_cap,
+    origins,
+    class_name,
+    class_type,
+    column,
+    desc_short,
+    primary_group,
+    secondary_group,
+    playable,
+    recharge_cap,
+    damage_cap,
+    recovery_cap,
+    regen_cap,
+    base_recovery,
+    base_regen,
+    base_threat,
+    perception_cap,
+});
+
+fields!(Powerset {
+    display_name,
+    nid_archetype,
+    set_type,
+    image_name,
+    full_name,
+    set_name,
+    description,
+    sub_name,
+    at_class,
+    uid_trunk_set,
+    uid_link_secondary,
+});
+
+fields!(Power {
+    static_index,
+    full_name,
+    group_name,
+    set_name,
+    power_name,
+    display_name,
+    available,
+    modes_required,
+    modes_disallowed,
+    power_type,
+    accuracy,
+    attack_types,
+    group_membership,
+    entities_affected,
+    entities_auto_hit,
+    target,
+    target_lo_s,
+    range,
+    target_secondary,
+    range_secondary,
+    end_cost,
+    interrupt_time,
+    cast_time,
+    recharge_time,
+    base_recharge_time,
+    activate_period,
+    effect_area,
+    radius,
+    arc,
+    max_targets,
+    max_boosts,
+    cast_flags,
+    ai_report,
+    num_charges,
+    usage_time,
+    life_time,
+    life_time_in_game,
+    num_allowed,
+    do_not_save,
+    boosts_allowed,
+    cast_through_hold,
+    ignore_strength,
+    desc_short,
+    desc_long,
+    enhancements,
+    set_types,
+    click_buff,
+    always_toggle,
+    level,
+    allow_front_loading,
+    variable_enabled,
+    variable_override,
+    variable_name,
+    variable_min,
+    variable_max,
+    uid_sub_power,
+    ignore_enh,
+    ignore_buff,
+    skip_max,
+    inherent_type,
+    display_location,
+    mutex_auto,
+    mutex_ignore,
+    absorb_summon_effects,
+    absorb_summon_attributes,
+    show_summon_anyway,
+    never_auto_update,
+    never_auto_update_requirements,
+    include_flag,
+    forced_class,
+    sort_override,
+    boost_boostable,
+    boost_use_player_level,
+    hidden_power,
+    active,
+    taken,
+    stacks,
+    variable_start,
+});
+
+fields!(Effect {
+    power_full_name,
+    unique_id,
+    effect_class,
+    effect_type,
+    damage_type,
+    mez_type,
+    et_modifies,
+    summon,
+    delayed_time,
+    ticks,
+    stacking,
+    base_probability,
+    suppression,
+    buffable,
+    resistible,
+    special_case,
+    variable_modified_override,
+    ignore_scaling,
+    pv_mode,
+    to_who,
+    display_percentage_override,
+    scale,
+    n_magnitude,
+    n_duration,
+    attrib_type,
+    aspect,
+    modifier_table,
+    near_ground,
+    cancel_on_miss,
+    requires_to_hit_check,
+    uid_class_name,
+    n_id_class_name,
+    expression_duration,
+    expression_magnitude,
+    expression_probability,
+    reward,
+    effect_id,
+    ignore_ed,
+    override_,
+    procs_per_minute,
+    power_attribs,
+    atr_orig_accuracy,
+    atr_orig_activate_period,
+    atr_orig_arc,
+    atr_orig_cast_time,
+    atr_orig_effect_area,
+    atr_orig_endurance_cost,
+    atr_orig_interrupt_time,
+    atr_orig_max_targets,
+    atr_orig_radius,
+    atr_orig_range,
+    atr_orig_recharge_time,
+    atr_orig_secondary_range,
+    atr_mod_accuracy,
+    atr_mod_activate_period,
+    atr_mod_arc,
+    atr_mod_cast_time,
+    atr_mod_effect_area,
+    atr_mod_endurance_cost,
+    atr_mod_interrupt_time,
+    atr_mod_max_targets,
+    atr_mod_radius,
+    atr_mod_range,
+    atr_mod_recharge_time,
+    atr_mod_secondary_range,
+});
+
+fields!(Summon {
+    uid,
+    display_name,
+    entity_type,
+    class_name,
+    powerset_full_name,
+    upgrade_power_full_name,
+});
@@ -1,4 +1,5 @@
 use std::{
+    collections::HashMap,
     fmt::Debug,
     io::{Read, Write},
 };
@@ -8,6 +9,7 @@ use serde::{Deserialize, Serialize};
 use crate::netbinary;
 
 pub mod enums;
+pub mod fields;
 use enums::*;
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -250,6 +252,98 @@ pub struct Summon {
     pub upgrade_power_full_name: Vec<String>,
 }
 
+/// Case-insensitive lookups from the string ids used in cross-references to table positions.
+pub struct Indices {
+    pub archetypes: HashMap<String, usize>, // by class_name
+    pub powersets: HashMap<String, usize>,  // by full_name
+    pub powers: HashMap<String, usize>,     // by full_name
+    pub summons: HashMap<String, usize>,    // by uid
+}
+
+impl Indices {
+    fn build<'a>(names: impl Iterator<Item = &'a String>) -> HashMap<String, usize> {
+        let mut map = HashMap::new();
+        for (i, n) in names.enumerate() {
+            // Like Mids, the first entry wins if there are duplicates.
+            map.entry(n.to_ascii_lowercase()).or_insert(i);
+        }
+        map
+    }
+
+    pub fn archetype(&self, class_name: &str) -> Option<usize> {
+        self.archetypes.get(&class_name.to_ascii_lowercase()).copied()
+    }
+
+    pub fn powerset(&self, full_name: &str) -> Option<usize> {
+        self.powersets.get(&full_name.to_ascii_lowercase()).copied()
+    }
+
+    pub fn power(&self, full_name: &str) -> Option<usize> {
+        self.powers.get(&full_name.to_ascii_lowercase()).copied()
+    }
+
+    pub fn summon(&self, uid: &str) -> Option<usize> {
+        self.summons.get(&uid.to_ascii_lowercase()).copied()
+    }
+}
+
+impl Effect {
+    /// The power a PowerRedirect or GrantPower effect refers to.
+    pub fn redirect_target(&self) -> &str {
+        if self.override_.is_empty() {
+            &self.summon
+        } else {
+            &self.override_
+        }
+    }
+}
+
+impl Database {
+    pub fn indices(&self) -> Indices {
+        Indices {
+            archetypes: Indices::build(self.archetypes.iter().map(|a| &a.class_name)),
+            powersets: Indices::build(self.powersets.iter().map(|p| &p.full_name)),
+            powers: Indices::build(self.powers.iter().map(|p| &p.full_name)),
+            summons: Indices::build(self.summons.iter().map(|s| &s.uid)),
+        }
+    }
+
+    /// Recomputes the numeric ids stored alongside string ids (Powerset::nid_archetype, MutexSet::nid
+    /// and Effect::n_id_class_name), returning a description of each one that changed.
+    pub fn rebuild_nids(&mut self) -> Vec<String> {
+        let idx = self.indices();
+        let nid = |found: Option<usize>| found.map_or(-1, |i| i as i32);
+        let mut changes = Vec::new();
+        for ps in &mut self.powersets {
+            let n = nid(idx.archetype(&ps.at_class));
+            if ps.nid_archetype != n {
+                changes.push(format!("{}: nid_archetype {} renumbered to {}", ps.full_name, ps.nid_archetype, n));
+                ps.nid_archetype = n;
+            }
+            for m in &mut ps.mutex_sets {
+                let n = nid(idx.powerset(&m.uid));
+                if m.nid != n {
+                    changes.push(format!("{}: mutex_sets[{}].nid {} renumbered to {}", ps.full_name, m.uid, m.nid, n));
+                    m.nid = n;
+                }
+            }
+        }
+        for p in &mut self.powers {
+            for e in &mut p.effects {
+                let n = nid(idx.archetype(&e.uid_class_name));
+                if e.n_id_class_name != n {
+                    changes.push(format!(
+                        "{} effect {}: n_id_class_name {} renumbered to {}",
+                        p.full_name, e.unique_id, e.n_id_class_name, n
+                    ));
+                    e.n_id_class_name = n;
+                }
+            }
+        }
+        changes
+    }
+}
+
 pub fn from_reader<R: Read>(mut reader: R) -> netbinary::Result<Database> {
     if netbinary::read_bytes(&mut reader)? != b"Mids Reborn Powers Database" {
         return Err(netbinary::Error::Custom("wrong database type (must choose I12.mhd)".to_string()));
@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+
+use crate::mids::{self, enums::EffectType};
+
+/// Converts a lowercase CoD name component such as "shock_therapy" to Mids style ("Shock_Therapy").
+fn mids_case(name: &str) -> String {
+    name.split('_')
+        .map(|w| {
+            let mut c = w.chars();
+            c.next().map(|f| f.to_ascii_uppercase().to_string() + c.as_str()).unwrap_or_default()
+        })
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+struct Renamer {
+    // Lowercase Mids powerset full name -> new Mids powerset full name.
+    sets: BTreeMap<String, String>,
+}
+
+impl Renamer {
+    fn set(&self, name: &mut String) -> bool {
+        match self.sets.get(&name.to_ascii_lowercase()) {
+            Some(new) => {
+                new.clone_into(name);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn power(&self, name: &mut String) -> bool {
+        let Some((set, power)) = name.rsplit_once('.') else {
+            return false;
+        };
+        match self.sets.get(&set.to_ascii_lowercase()) {
+            Some(new) => {
+                *name = format!("{}.{}", new, power);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Renames Mids powersets and their powers to the CoD names given by `powerset_map` (lowercase Mids
+/// powerset full name -> CoD powerset full name), and rewrites every reference to them. Returns a
+/// line per renamed powerset and the number of names rewritten.
+pub fn rename_powersets(mdb: &mut mids::Database, powerset_map: &BTreeMap<&str, &str>) -> (Vec<String>, usize) {
+    let mut report = Vec::new();
+    let mut renamer = Renamer { sets: BTreeMap::new() };
+    for ps in &mdb.powersets {
+        let old = ps.full_name.to_ascii_lowercase();
+        let Some(&cod) = powerset_map.get(old.as_str()) else {
+            continue;
+        };
+        let (Some((old_group, _)), Some((new_group, new_set))) = (ps.full_name.split_once('.'), cod.split_once('.')) else {
+            continue;
+        };
+        // Keep the Mids spelling of the group if only the set was renamed.
+        let group = if old_group.eq_ignore_ascii_case(new_group) {
+            old_group.to_string()
+        } else {
+            mids_case(new_group)
+        };
+        let new = format!("{}.{}", group, mids_case(new_set));
+        report.push(format!("{}: renamed to {}", ps.full_name, new));
+        renamer.sets.insert(old, new);
+    }
+
+    let mut n = 0;
+    for ps in &mut mdb.powersets {
+        if renamer.set(&mut ps.full_name) {
+            let (_, set) = ps.full_name.split_once('.').unwrap();
+            ps.set_name = set.to_string();
+            n += 1;
+        }
+        n += usize::from(renamer.set(&mut ps.uid_trunk_set));
+        n += usize::from(renamer.set(&mut ps.uid_link_secondary));
+        for m in &mut ps.mutex_sets {
+            n += usize::from(renamer.set(&mut m.uid));
+        }
+    }
+    for p in &mut mdb.powers {
+        if renamer.power(&mut p.full_name) {
+            let mut parts = p.full_name.split('.');
+            p.group_name = parts.next().unwrap().to_string();
+            p.set_name = parts.next().unwrap().to_string();
+            n += 1;
+        }
+        // Renaming is an identity change, so requirements are rewritten even on powers marked
+        // never_auto_update_requirements; leaving them would make them dangle. They are reported so
+        // that the flag can be reviewed.
+        let mut requires = 0;
+        for (a, b) in p.requires.power_id.iter_mut().chain(&mut p.requires.power_id_not) {
+            requires += usize::from(renamer.power(a));
+            requires += usize::from(renamer.power(b));
+        }
+        if requires > 0 && p.never_auto_update_requirements {
+            report.push(format!("{}: renamed requirements despite never_auto_update_requirements", p.full_name));
+        }
+        n += requires;
+        for s in &mut p.uid_sub_power {
+            n += usize::from(renamer.power(s));
+        }
+        for e in &mut p.effects {
+            n += usize::from(renamer.power(&mut e.power_full_name));
+            // These refer to powers through summon/override_ (see Effect::redirect_target).
+            if matches!(e.effect_type, EffectType::PowerRedirect | EffectType::GrantPower) {
+                n += usize::from(renamer.power(&mut e.summon));
+                n += usize::from(renamer.power(&mut e.override_));
+            }
+        }
+    }
+    for s in &mut mdb.summons {
+        for ps in &mut s.powerset_full_name {
+            n += usize::from(renamer.set(ps));
+        }
+        for p in &mut s.upgrade_power_full_name {
+            n += usize::from(renamer.power(p));
+        }
+    }
+    (report, n)
+}
@@ -209,6 +209,11 @@ impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
     ) -> Result<Self::SerializeStructVariant> {
         Err(Error::UnsupportedType)
     }
+
+    #[inline]
+    fn is_human_readable(&self) -> bool {
+        false
+    }
 }
 
 impl<'a, W: Write> ser::SerializeSeq for &'a mut Serializer<W> {
@@ -468,6 +473,10 @@ pub mod array_hack {
             }
         }
 
+        // The length prefix is only part of the binary layout.
+        if serializer.is_human_readable() {
+            return value.serialize(serializer);
+        }
         let len32 = match i32::try_from(value.len()) {
             Ok(v) => v,
             Err(e) => return Err(S::Error::custom("array too large")),
@@ -530,6 +539,9 @@ pub mod array_hack {
             }
         }
 
+        if deserializer.is_human_readable() {
+            return Vec::deserialize(deserializer);
+        }
         deserializer.deserialize_tuple(2, HackVisitor(PhantomData))
     }
 }
@@ -0,0 +1,104 @@
+//! Per-power exceptions to the CoD sync. An override file is a JSON list of rules:
+//!
+//! ```json
+//! [
+//!     { "power": "Pool.Speed.Hasten", "skip": ["recharge_time"], "comment": "why" },
+//!     { "power": "Temporary_Powers.Temporary_Powers.Seismic_Shockwaves", "level": 0 },
+//!     { "power": "Pool.Flight.Fly", "ignore_set_types": ["Flight"] },
+//!     { "power": "Pets.Titan_Weapons.Defensive_Sweep_Fast", "cod_power": "pets.titan_weapons.defensive_sweep_fast" }
+//! ]
+//! ```
+
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use crate::{
+    export,
+    mids::{self, enums::SetType},
+};
+
+/// The parts of a power the sync can change.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncField {
+    All,
+    Boosts,
+    SetTypes,
+    Level,
+    RechargeTime,
+    CastTime,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// Full name of the Mids power.
+    pub power: String,
+    /// Fields to leave alone.
+    #[serde(default)]
+    pub skip: Vec<SyncField>,
+    /// Level to use instead of the CoD one.
+    pub level: Option<i32>,
+    /// Set types to neither add nor remove.
+    #[serde(default)]
+    pub ignore_set_types: Vec<String>,
+    /// CoD power to sync from, instead of looking it up by display name.
+    pub cod_power: Option<String>,
+    #[serde(default)]
+    pub comment: String,
+}
+
+impl Rule {
+    pub fn skips(&self, field: SyncField) -> bool {
+        self.skip.iter().any(|&f| f == field || f == SyncField::All)
+    }
+}
+
+#[derive(Default)]
+pub struct Overrides {
+    // By lowercase power full name.
+    rules: HashMap<String, Rule>,
+    ignore_set_types: HashMap<String, Vec<SetType>>,
+}
+
+impl Overrides {
+    pub fn load(path: &Path) -> Result<Self> {
+        let rules: Vec<Rule> = export::read_json(path)?;
+        let mut o = Overrides::default();
+        for r in rules {
+            let key = r.power.to_ascii_lowercase();
+            let sets = r.ignore_set_types.iter().map(|s| s.parse()).collect::<Result<Vec<SetType>, String>>();
+            let sets = sets.map_err(anyhow::Error::msg).with_context(|| format!("override for {}", r.power))?;
+            o.ignore_set_types.insert(key.clone(), sets);
+            if o.rules.insert(key, r).is_some() {
+                bail!("{}: more than one override for the same power", path.display());
+            }
+        }
+        Ok(o)
+    }
+
+    pub fn get(&self, power: &str) -> Option<&Rule> {
+        self.rules.get(&power.to_ascii_lowercase())
+    }
+
+    pub fn ignored_set_types(&self, power: &str) -> &[SetType] {
+        self.ignore_set_types.get(&power.to_ascii_lowercase()).map_or(&[], Vec::as_slice)
+    }
+
+    /// Warns about rules for powers that aren't in the database, which have probably gone stale.
+    pub fn warn_unmatched(&self, mdb: &mids::Database) {
+        let idx = mdb.indices();
+        let mut stale: Vec<&str> = self
+            .rules
+            .values()
+            .filter(|r| idx.power(&r.power).is_none())
+            .map(|r| r.power.as_str())
+            .collect();
+        stale.sort_unstable();
+        for s in stale {
+            println!("Warning: override for {} matches no power", s);
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//! Declarative hand fixes, replayed onto a database. A patch file is a JSON list of rules:
+//!
+//! ```json
+//! [
+//!     {
+//!         "target": "power",
+//!         "select": "full_name = Pool.Speed.Hasten",
+//!         "set": { "recharge_time": 450, "set_types": ["Heal"] },
+//!         "comment": "why this is needed"
+//!     }
+//! ]
+//! ```
+//!
+//! `select` is a query expression (see the query module) and `target` is one of power, effect,
+//! powerset, archetype or summon.
+
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    export,
+    mids::{
+        self,
+        fields::{Field, Fields, LIST_SEP},
+    },
+    query,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct Rule {
+    pub target: String,
+    pub select: String,
+    pub set: BTreeMap<String, Value>,
+    #[serde(default)]
+    pub comment: String,
+}
+
+pub fn load(path: &Path) -> Result<Vec<Rule>> {
+    export::read_json(path)
+}
+
+/// Converts a JSON value to the text form used by mids::fields.
+fn cell_text(v: &Value) -> String {
+    match v {
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items.iter().map(cell_text).collect::<Vec<_>>().join(&LIST_SEP.to_string()),
+        other => other.to_string(),
+    }
+}
+
+/// Applies one rule to the matching records, returning the number matched.
+fn apply_to<'a, T: Fields + 'a>(
+    records: impl Iterator<Item = &'a mut T>,
+    name: impl Fn(&T) -> String,
+    rule: &Rule,
+    changes: &mut Vec<String>,
+) -> Result<usize> {
+    let expr = query::parse::<T>(&rule.select)?;
+    let assignments: Vec<(&Field<T>, String)> = rule
+        .set
+        .iter()
+        .map(|(k, v)| Ok((T::field(k).ok_or_else(|| anyhow!("unknown field {:?}", k))?, cell_text(v))))
+        .collect::<Result<_>>()?;
+    let mut matched = 0;
+    for r in records.filter(|r| expr.matches(r)) {
+        matched += 1;
+        for (field, value) in &assignments {
+            let old = (field.get)(r);
+            (field.set)(r, value).map_err(|e| anyhow!("{}: {}: {}", name(r), field.name, e))?;
+            let new = (field.get)(r);
+            if old != new {
+                changes.push(format!("{}: {} {} patched to {}", name(r), field.name, old, new));
+            }
+        }
+    }
+    Ok(matched)
+}
+
+/// Applies the rules in order, returning a description of each field changed, followed by the rule's
+/// comment if it has one. Rules that match nothing are warned about, as they have probably gone stale.
+pub fn apply(mdb: &mut mids::Database, rules: &[Rule]) -> Result<Vec<String>> {
+    let mut changes = Vec::new();
+    for rule in rules {
+        let before = changes.len();
+        let matched = match rule.target.as_str() {
+            "power" => apply_to(mdb.powers.iter_mut(), |p| p.full_name.clone(), rule, &mut changes),
+            "effect" => apply_to(
+                mdb.powers.iter_mut().flat_map(|p| &mut p.effects),
+                |e| format!("{} effect {}", e.power_full_name, e.unique_id),
+                rule,
+                &mut changes,
+            ),
+            "powerset" => apply_to(mdb.powersets.iter_mut(), |p| p.full_name.clone(), rule, &mut changes),
+            "archetype" => apply_to(mdb.archetypes.iter_mut(), |a| a.class_name.clone(), rule, &mut changes),
+            "summon" => apply_to(mdb.summons.iter_mut(), |s| s.uid.clone(), rule, &mut changes),
+            t => bail!("unknown patch target {:?}", t),
+        }
+        .with_context(|| format!("patch rule {} {:?}", rule.target, rule.select))?;
+        if matched == 0 {
+            println!("Warning: patch rule {} {:?} matched nothing", rule.target, rule.select);
+        }
+        if !rule.comment.is_empty() {
+            for c in &mut changes[before..] {
+                c.push_str(&format!(" ({})", rule.comment));
+            }
+        }
+    }
+    Ok(changes)
+}
@@ -0,0 +1,362 @@
+//! Filter expressions over the fields of Mids records, for example
+//! `power_type = Toggle and recharge_time > 0 and group_name = Pool` or
+//! `set_types contains MeleeAoE`.
+//!
+//! Comparisons are numeric when both sides are numbers and case-insensitive otherwise. `contains`
+//! tests membership for list fields (including enum flag sets) and substrings for others.
+
+use std::{cmp::Ordering, io::Write};
+
+use anyhow::{anyhow, bail, Result};
+use serde::Serialize;
+
+use crate::mids::fields::{Field, Fields, LIST_SEP};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+pub enum Expr<T: 'static> {
+    And(Box<Expr<T>>, Box<Expr<T>>),
+    Or(Box<Expr<T>>, Box<Expr<T>>),
+    Not(Box<Expr<T>>),
+    Cmp { field: &'static Field<T>, op: Op, value: String },
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Op(Op),
+    Open,
+    Close,
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' => {
+                chars.next();
+                tokens.push(if c == '(' { Token::Open } else { Token::Close });
+            }
+            '"' => {
+                chars.next();
+                let mut q = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => q.push(c),
+                        None => bail!("unterminated string in {:?}", s),
+                    }
+                }
+                tokens.push(Token::Quoted(q));
+            }
+            '=' | '!' | '<' | '>' => {
+                chars.next();
+                let eq = chars.next_if_eq(&'=').is_some();
+                tokens.push(Token::Op(match (c, eq) {
+                    ('=', _) => Op::Eq,
+                    ('!', true) => Op::Ne,
+                    ('<', false) => Op::Lt,
+                    ('<', true) => Op::Le,
+                    ('>', false) => Op::Gt,
+                    ('>', true) => Op::Ge,
+                    _ => bail!("unexpected '!' in {:?}", s),
+                }));
+            }
+            _ => {
+                let mut w = String::new();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && !"()\"=!<>".contains(*c)) {
+                    w.push(c);
+                }
+                tokens.push(match w.to_ascii_lowercase().as_str() {
+                    "contains" => Token::Op(Op::Contains),
+                    _ => Token::Word(w),
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn keyword(&mut self, kw: &str) -> bool {
+        match self.tokens.get(self.pos) {
+            Some(Token::Word(w)) if w.eq_ignore_ascii_case(kw) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        self.pos += 1;
+        self.tokens.get(self.pos - 1)
+    }
+
+    fn or<T: Fields>(&mut self) -> Result<Expr<T>> {
+        let mut e = self.and()?;
+        while self.keyword("or") {
+            e = Expr::Or(Box::new(e), Box::new(self.and()?));
+        }
+        Ok(e)
+    }
+
+    fn and<T: Fields>(&mut self) -> Result<Expr<T>> {
+        let mut e = self.not()?;
+        while self.keyword("and") {
+            e = Expr::And(Box::new(e), Box::new(self.not()?));
+        }
+        Ok(e)
+    }
+
+    fn not<T: Fields>(&mut self) -> Result<Expr<T>> {
+        if self.keyword("not") {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        if self.tokens.get(self.pos) == Some(&Token::Open) {
+            self.pos += 1;
+            let e = self.or()?;
+            if self.next() != Some(&Token::Close) {
+                bail!("missing ')'");
+            }
+            return Ok(e);
+        }
+        self.cmp()
+    }
+
+    fn cmp<T: Fields>(&mut self) -> Result<Expr<T>> {
+        let field = match self.next() {
+            Some(Token::Word(w)) => T::field(w).ok_or_else(|| anyhow!("unknown field {:?}", w))?,
+            t => bail!("expected a field name, found {:?}", t),
+        };
+        let op = match self.next() {
+            Some(&Token::Op(op)) => op,
+            t => bail!("expected an operator after {}, found {:?}", field.name, t),
+        };
+        let value = match self.next() {
+            Some(Token::Word(w) | Token::Quoted(w)) => w.clone(),
+            t => bail!("expected a value after {} {:?}, found {:?}", field.name, op, t),
+        };
+        Ok(Expr::Cmp { field, op, value })
+    }
+}
+
+pub fn parse<T: Fields>(s: &str) -> Result<Expr<T>> {
+    let mut p = Parser {
+        tokens: tokenize(s)?,
+        pos: 0,
+    };
+    let e = p.or()?;
+    if let Some(t) = p.tokens.get(p.pos) {
+        bail!("unexpected {:?} in {:?}", t, s);
+    }
+    Ok(e)
+}
+
+fn compare(a: &str, b: &str) -> Ordering {
+    match (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+        (Ok(x), Ok(y)) => x.total_cmp(&y),
+        _ => a.to_ascii_lowercase().cmp(&b.to_ascii_lowercase()),
+    }
+}
+
+impl<T> Expr<T> {
+    pub fn matches(&self, v: &T) -> bool {
+        match self {
+            Expr::And(a, b) => a.matches(v) && b.matches(v),
+            Expr::Or(a, b) => a.matches(v) || b.matches(v),
+            Expr::Not(a) => !a.matches(v),
+            Expr::Cmp { field, op, value } => {
+                let text = (field.get)(v);
+                match op {
+                    Op::Contains if field.list => text.split(LIST_SEP).any(|i| compare(i, value).is_eq()),
+                    Op::Contains => text.to_ascii_lowercase().contains(&value.to_ascii_lowercase()),
+                    Op::Eq => compare(&text, value).is_eq(),
+                    Op::Ne => compare(&text, value).is_ne(),
+                    Op::Lt => compare(&text, value).is_lt(),
+                    Op::Le => compare(&text, value).is_le(),
+                    Op::Gt => compare(&text, value).is_gt(),
+                    Op::Ge => compare(&text, value).is_ge(),
+                }
+            }
+        }
+    }
+
+    /// Names of the fields the expression looks at, in order of first use.
+    pub fn field_names(&self, names: &mut Vec<&'static str>) {
+        match self {
+            Expr::And(a, b) | Expr::Or(a, b) => {
+                a.field_names(names);
+                b.field_names(names);
+            }
+            Expr::Not(a) => a.field_names(names),
+            Expr::Cmp { field, .. } => {
+                if !names.contains(&field.name) {
+                    names.push(field.name);
+                }
+            }
+        }
+    }
+}
+
+/// Writes the records matching `expr`, either as whole JSON records or as a tab-separated table
+/// of `columns`.
+pub fn run<'a, T: Fields + Serialize + 'a, W: Write>(
+    records: impl Iterator<Item = &'a T>,
+    expr: &Expr<T>,
+    columns: &[&str],
+    json: bool,
+    writer: W,
+) -> Result<usize> {
+    let matching: Vec<&T> = records.filter(|r| expr.matches(r)).collect();
+    if json {
+        serde_json::to_writer_pretty(writer, &matching)?;
+        return Ok(matching.len());
+    }
+    let cols: Vec<&Field<T>> = columns
+        .iter()
+        .map(|n| T::field(n).ok_or_else(|| anyhow!("unknown field {:?}", n)))
+        .collect::<Result<_>>()?;
+    let mut w = csv::WriterBuilder::new().delimiter(b'\t').from_writer(writer);
+    w.write_record(columns)?;
+    for r in &matching {
+        w.write_record(cols.iter().map(|c| (c.get)(r)))?;
+    }
+    w.flush()?;
+    Ok(matching.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mids::{
+        enums::{CastFlags, CastKind},
+        fields::Cell,
+    };
+
+    struct Rec {
+        name: String,
+        level: i32,
+        cast_flags: CastFlags,
+        boosts: Vec<String>,
+    }
+
+    impl Fields for Rec {
+        const FIELDS: &'static [Field<Self>] = &[
+            Field {
+                name: "name",
+                list: false,
+                get: |r| r.name.to_cell(),
+                set: |r, s| {
+                    r.name = Cell::from_cell(s)?;
+                    Ok(())
+                },
+            },
+            Field {
+                name: "level",
+                list: false,
+                get: |r| r.level.to_cell(),
+                set: |r, s| {
+                    r.level = Cell::from_cell(s)?;
+                    Ok(())
+                },
+            },
+            Field {
+                name: "cast_flags",
+                list: true,
+                get: |r| r.cast_flags.to_cell(),
+                set: |r, s| {
+                    r.cast_flags = Cell::from_cell(s)?;
+                    Ok(())
+                },
+            },
+            Field {
+                name: "boosts",
+                list: true,
+                get: |r| r.boosts.to_cell(),
+                set: |r, s| {
+                    r.boosts = Cell::from_cell(s)?;
+                    Ok(())
+                },
+            },
+        ];
+    }
+
+    fn rec() -> Rec {
+        Rec {
+            name: "Pool.Speed.Hasten".to_string(),
+            level: 14,
+            cast_flags: CastFlags::from_iter([CastKind::NearGround, CastKind::CastableAfterDeath]),
+            boosts: vec!["Recharge".to_string(), "EnduranceDiscount".to_string()],
+        }
+    }
+
+    fn matches(expr: &str) -> bool {
+        parse::<Rec>(expr).unwrap().matches(&rec())
+    }
+
+    #[test]
+    fn precedence() {
+        // and binds tighter than or: true or (false and false).
+        assert!(matches("level = 14 or level = 1 and level = 2"));
+        assert!(!matches("(level = 14 or level = 1) and level = 2"));
+        assert!(!matches("not level = 14"));
+        assert!(matches("not level = 1 and not (level = 2 or level = 3)"));
+    }
+
+    #[test]
+    fn comparisons() {
+        assert!(matches("level != 15"));
+        assert!(!matches("level != 14"));
+        assert!(matches("level <= 14") && matches("level >= 14"));
+        assert!(!matches("level <= 13") && !matches("level >= 15"));
+        assert!(matches("level < 15") && matches("level > 13"));
+    }
+
+    #[test]
+    fn contains() {
+        assert!(matches("cast_flags contains NearGround"));
+        assert!(matches("cast_flags contains castableafterdeath"));
+        assert!(!matches("cast_flags contains TargetNearGround"));
+        assert!(matches("boosts contains recharge"));
+        // List fields match whole items, not substrings.
+        assert!(!matches("boosts contains Endurance"));
+        assert!(matches("name contains speed"));
+    }
+
+    #[test]
+    fn numbers_and_text() {
+        // Numerically 14 < 9 is false, though "14" < "9" as text.
+        assert!(!matches("level < 9"));
+        assert!(matches("level = 14.0"));
+        assert!(matches("name = pool.speed.hasten"));
+        assert!(matches("name = \"Pool.Speed.Hasten\""));
+        assert!(matches("name > Pool.Speed.Flurry"));
+    }
+
+    #[test]
+    fn errors() {
+        for bad in ["level =", "level =! 1", "name = \"Pool", "nope = 1", "(level = 1", "level = 1 level"] {
+            assert!(parse::<Rec>(bad).is_err(), "{:?}", bad);
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+use anyhow::Result;
+
+use crate::{cod, export, mids};
+
+pub struct Proposal {
+    pub mids_set: String,
+    pub cod_set: String,
+    pub score: f32,
+}
+
+/// Reads a powerset map file: a JSON object from Mids powerset full names to CoD ones, such as
+/// `{ "controller_buff.electrical_affinity": "controller_buff.shock_therapy" }`. Names are
+/// lowercased.
+pub fn load_powerset_map(path: &Path) -> Result<BTreeMap<String, String>> {
+    let map: BTreeMap<String, String> = export::read_json(path)?;
+    Ok(map.into_iter().map(|(m, c)| (m.to_ascii_lowercase(), c.to_ascii_lowercase())).collect())
+}
+
+/// Names that identify a power within its set: the display name and the short name.
+fn member_keys<'a>(display_names: impl Iterator<Item = &'a str>, full_names: impl Iterator<Item = &'a str>) -> BTreeSet<String> {
+    let mut keys = BTreeSet::new();
+    keys.extend(display_names.map(|d| format!("d:{}", d)));
+    keys.extend(
+        full_names
+            .filter_map(|n| n.rsplit('.').next())
+            .map(|n| format!("s:{}", n.to_ascii_lowercase())),
+    );
+    keys
+}
+
+/// Proposes CoD counterparts for Mids powersets that don't appear in the CoD index (after applying
+/// `powerset_map`), scored by the Jaccard similarity of their member power names and display names.
+/// Only CoD powersets in the same category that no Mids powerset already matches are considered.
+pub fn propose_powerset_renames(mdb: &mids::Database, cidx: &cod::Index, powerset_map: &BTreeMap<&str, &str>, min_score: f32) -> Vec<Proposal> {
+    let mut mids_sets: BTreeMap<String, Vec<&mids::Power>> = BTreeMap::new();
+    for p in &mdb.powers {
+        if let Some((set, _)) = p.full_name.rsplit_once('.') {
+            mids_sets.entry(set.to_ascii_lowercase()).or_default().push(p);
+        }
+    }
+
+    let matched: BTreeSet<&str> = mids_sets
+        .keys()
+        .map(|s| *powerset_map.get(s.as_str()).unwrap_or(&s.as_str()))
+        .filter(|s| cidx.contains_key(*s))
+        .collect();
+
+    let mut proposals = Vec::new();
+    for (set, powers) in &mids_sets {
+        let mapped = *powerset_map.get(set.as_str()).unwrap_or(&set.as_str());
+        if cidx.contains_key(mapped) {
+            continue;
+        }
+        let Some((category, _)) = set.split_once('.') else {
+            continue;
+        };
+        let mids_keys = member_keys(
+            powers.iter().map(|p| p.display_name.as_str()),
+            powers.iter().map(|p| p.full_name.as_str()),
+        );
+        let mut best: Option<Proposal> = None;
+        for (cod_set, sidx) in cidx {
+            if matched.contains(cod_set.as_str()) || cod_set.split_once('.').map(|(c, _)| c) != Some(category) {
+                continue;
+            }
+            let cod_keys = member_keys(sidx.keys().map(String::as_str), sidx.values().flatten().map(String::as_str));
+            let common = mids_keys.intersection(&cod_keys).count();
+            let score = common as f32 / (mids_keys.len() + cod_keys.len() - common) as f32;
+            if score >= min_score && best.as_ref().is_none_or(|b| score > b.score) {
+                best = Some(Proposal {
+                    mids_set: set.clone(),
+                    cod_set: cod_set.clone(),
+                    score,
+                });
+            }
+        }
+        proposals.extend(best);
+    }
+    proposals
+}
@@ -0,0 +1,153 @@
+//! Summons (pets and henchmen), synced from the CoD entities they're created from.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+    cod,
+    mids::{self, enums::EffectType},
+};
+
+/// The summon uids referenced by EntCreate effects, with the power referencing each.
+pub fn references(mdb: &mids::Database) -> Vec<(&str, &str)> {
+    mdb.powers
+        .iter()
+        .flat_map(|p| &p.effects)
+        .filter(|e| e.effect_type == EffectType::EntCreate && !e.summon.is_empty())
+        .map(|e| (e.summon.as_str(), e.power_full_name.as_str()))
+        .collect()
+}
+
+/// Resolves the powers of each summon's powersets to CoD powers through the summon's entity, rather
+/// than by display name. Each Mids powerset is paired with the entity powerset of the same (mapped)
+/// name, or failing that the one in the same position, and each power with the power of the same
+/// short name or, failing that, display name. Returns the CoD full name by Mids power position for
+/// every power in a summon's powersets, None where it couldn't be resolved, and a description of
+/// each problem.
+pub fn pet_powers(
+    mdb: &mids::Database,
+    entities: &HashMap<String, cod::Entity>,
+    powerset_map: &BTreeMap<&str, &str>,
+    cidx: &cod::Index,
+) -> (HashMap<usize, Option<String>>, Vec<String>) {
+    let mut resolved = HashMap::new();
+    let mut report = Vec::new();
+
+    // Lowercase Mids powerset full name -> positions of its powers.
+    let mut set_powers: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, p) in mdb.powers.iter().enumerate() {
+        if let Some((set, _)) = p.full_name.rsplit_once('.') {
+            set_powers.entry(set.to_ascii_lowercase()).or_default().push(i);
+        }
+    }
+
+    for s in &mdb.summons {
+        for set in &s.powerset_full_name {
+            for &i in set_powers.get(&set.to_ascii_lowercase()).map_or(&[][..], Vec::as_slice) {
+                resolved.entry(i).or_insert(None);
+            }
+        }
+        let Some(ent) = entities.get(&s.uid.to_ascii_lowercase()) else {
+            continue;
+        };
+        let cod_sets: Vec<String> = ent.powerset_names.iter().map(|n| n.to_ascii_lowercase()).collect();
+        for (pos, set) in s.powerset_full_name.iter().enumerate() {
+            let nl = set.to_ascii_lowercase();
+            let mapped = powerset_map.get(nl.as_str()).copied().unwrap_or(&nl);
+            let cod_set = match cod_sets.iter().find(|c| c.as_str() == mapped) {
+                Some(c) => c,
+                None if cod_sets.len() == s.powerset_full_name.len() => &cod_sets[pos],
+                None => {
+                    report.push(format!("Summon {}: no CoD powerset for {}", s.uid, set));
+                    continue;
+                }
+            };
+            let Some(sidx) = cidx.get(cod_set) else {
+                report.push(format!("Summon {}: CoD powerset {} isn't in the index", s.uid, cod_set));
+                continue;
+            };
+            for &i in set_powers.get(&nl).map_or(&[][..], Vec::as_slice) {
+                let p = &mdb.powers[i];
+                let short = p.power_name.to_ascii_lowercase();
+                let by_short = sidx.values().flatten().find(|n| n.rsplit('.').next() == Some(short.as_str()));
+                let by_display = sidx.get(&p.display_name).and_then(|names| match names.as_slice() {
+                    [only] => Some(only),
+                    _ => None,
+                });
+                match by_short.or(by_display) {
+                    Some(n) => {
+                        resolved.insert(i, Some(n.clone()));
+                    }
+                    None => report.push(format!(
+                        "{} ({}): no matching power in CoD {} (summon {})",
+                        p.full_name, p.display_name, cod_set, s.uid
+                    )),
+                }
+            }
+        }
+    }
+    (resolved, report)
+}
+
+/// Fixes the display name, class and powersets of each summon from its CoD entity, which is found
+/// by lowercase uid in `entities`. Also reports EntCreate effects that reference summons Mids doesn't have.
+/// Returns a description of each fix and problem, and whether anything was changed.
+pub fn sync(mdb: &mut mids::Database, entities: &HashMap<String, cod::Entity>, powerset_map: &BTreeMap<&str, &str>) -> (Vec<String>, bool) {
+    let mut report = Vec::new();
+    let mut changed = false;
+
+    // CoD powerset name -> Mids powerset full name.
+    let mut sets = HashMap::new();
+    for ps in &mdb.powersets {
+        let nl = ps.full_name.to_ascii_lowercase();
+        let cod_name = powerset_map.get(nl.as_str()).map_or(nl.clone(), |s| s.to_string());
+        sets.insert(cod_name, ps.full_name.clone());
+    }
+
+    for s in &mut mdb.summons {
+        let Some(ent) = entities.get(&s.uid.to_ascii_lowercase()) else {
+            report.push(format!("Summon {}: no CoD entity", s.uid));
+            continue;
+        };
+        if !ent.display_name.is_empty() && s.display_name != ent.display_name {
+            report.push(format!(
+                "Summon {}: display_name {:?} fixed to {:?}",
+                s.uid, s.display_name, ent.display_name
+            ));
+            s.display_name.clone_from(&ent.display_name);
+            changed = true;
+        }
+        if !ent.class_name.is_empty() && !s.class_name.eq_ignore_ascii_case(&ent.class_name) {
+            report.push(format!("Summon {}: class_name {} fixed to {}", s.uid, s.class_name, ent.class_name));
+            s.class_name.clone_from(&ent.class_name);
+            changed = true;
+        }
+
+        let mut cod_sets = Vec::with_capacity(ent.powerset_names.len());
+        for name in &ent.powerset_names {
+            match sets.get(&name.to_ascii_lowercase()) {
+                Some(mids_name) => cod_sets.push(mids_name.clone()),
+                None => report.push(format!("Summon {}: CoD powerset {} isn't in Mids", s.uid, name)),
+            }
+        }
+        // Only rewrite the list if every CoD powerset was found, or the summon would lose powers.
+        if cod_sets.len() == ent.powerset_names.len() && !cod_sets.is_empty() && s.powerset_full_name != cod_sets {
+            report.push(format!("Summon {}: powersets {:?} fixed to {:?}", s.uid, s.powerset_full_name, cod_sets));
+            s.powerset_full_name = cod_sets;
+            changed = true;
+        }
+    }
+
+    let idx = mdb.indices();
+    let mut missing: Vec<(&str, &str)> = references(mdb).into_iter().filter(|(uid, _)| idx.summon(uid).is_none()).collect();
+    missing.sort_unstable();
+    missing.dedup();
+    for (uid, power) in missing {
+        let hint = if entities.contains_key(&uid.to_ascii_lowercase()) {
+            " (CoD has it)"
+        } else {
+            ""
+        };
+        report.push(format!("{}: EntCreate references missing summon {}{}", power, uid, hint));
+    }
+    (report, changed)
+}
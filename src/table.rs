@@ -0,0 +1,75 @@
+use std::io::{Read, Write};
+
+use anyhow::{bail, Result};
+
+use crate::mids::{
+    self,
+    fields::{Field, Fields},
+};
+
+/// Columns exported when none are asked for.
+pub const DEFAULT_COLUMNS: &[&str] = &[
+    "full_name",
+    "display_name",
+    "power_type",
+    "level",
+    "recharge_time",
+    "cast_time",
+    "end_cost",
+    "set_types",
+];
+
+fn columns(names: &[&str]) -> Result<Vec<&'static Field<mids::Power>>> {
+    names
+        .iter()
+        .map(|n| mids::Power::field(n).ok_or_else(|| anyhow::anyhow!("unknown power field {:?}", n)))
+        .collect()
+}
+
+/// Writes one row per power with the given columns. Enums are written by name, and lists as
+/// mids::fields::LIST_SEP-separated items.
+pub fn export<W: Write>(mdb: &mids::Database, names: &[&str], delimiter: u8, writer: W) -> Result<()> {
+    let cols = columns(names)?;
+    let mut w = csv::WriterBuilder::new().delimiter(delimiter).from_writer(writer);
+    w.write_record(names)?;
+    for p in &mdb.powers {
+        w.write_record(cols.iter().map(|c| (c.get)(p)))?;
+    }
+    w.flush()?;
+    Ok(())
+}
+
+/// Applies a table written by export back to the matching powers, which are found by the
+/// full_name column. Returns a description of each field changed.
+pub fn import<R: Read>(mdb: &mut mids::Database, delimiter: u8, reader: R) -> Result<Vec<String>> {
+    let mut r = csv::ReaderBuilder::new().delimiter(delimiter).from_reader(reader);
+    let headers: Vec<String> = r.headers()?.iter().map(str::to_string).collect();
+    let Some(key) = headers.iter().position(|h| h == "full_name") else {
+        bail!("table has no full_name column");
+    };
+    let cols = columns(&headers.iter().map(String::as_str).collect::<Vec<_>>())?;
+    let idx = mdb.indices();
+    let mut changes = Vec::new();
+    for (line, row) in r.records().enumerate() {
+        let row = row?;
+        let Some(i) = idx.power(&row[key]) else {
+            bail!("row {}: no power named {:?}", line + 2, &row[key]);
+        };
+        let p = &mut mdb.powers[i];
+        for (c, value) in cols.iter().zip(row.iter()) {
+            let old = (c.get)(p);
+            if old == value {
+                continue;
+            }
+            if let Err(e) = (c.set)(p, value) {
+                bail!("row {}: {}: {}", line + 2, c.name, e);
+            }
+            // Compare normalised forms so that e.g. "1.50" vs "1.5" isn't a change.
+            let new = (c.get)(p);
+            if new != old {
+                changes.push(format!("{}: {} {:?} changed to {:?}", p.full_name, c.name, old, new));
+            }
+        }
+    }
+    Ok(changes)
+}
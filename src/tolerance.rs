@@ -0,0 +1,91 @@
+//! How closely Mids values have to match CoD before the sync leaves them alone. A tolerance file is
+//! a JSON object with optional entries per field, for example:
+//!
+//! ```json
+//! {
+//!     "recharge_time": { "abs": 0.01, "rel": 0.001 },
+//!     "cast_time": { "abs": 0.001, "tick": 0.033 }
+//! }
+//! ```
+//!
+//! Values within tolerance are reported as near misses rather than changed.
+
+use std::path::Path;
+
+use anyhow::Result;
+use serde::Deserialize;
+
+use crate::export;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Tolerance {
+    /// Largest absolute difference to ignore.
+    pub abs: f32,
+    /// Largest difference to ignore, as a fraction of the CoD value.
+    pub rel: f32,
+    /// If set, CoD values are rounded to a multiple of this (e.g. the server tick) before use.
+    pub tick: Option<f32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Match {
+    Exact,
+    Near,
+    Differs,
+}
+
+impl Tolerance {
+    /// The value Mids should have for the CoD value `cod`.
+    pub fn expected(&self, cod: f32) -> f32 {
+        match self.tick {
+            Some(t) if t > 0.0 => (cod / t).round() * t,
+            _ => cod,
+        }
+    }
+
+    pub fn compare(&self, mids: f32, cod: f32) -> Match {
+        let cod = self.expected(cod);
+        let d = (mids - cod).abs();
+        if d == 0.0 {
+            Match::Exact
+        } else if d <= self.abs || d <= self.rel * cod.abs() {
+            Match::Near
+        } else {
+            Match::Differs
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Tolerances {
+    pub recharge_time: Tolerance,
+    pub cast_time: Tolerance,
+    pub accuracy: Tolerance,
+    /// Enhancement values of Boosts powers.
+    pub scale: Tolerance,
+}
+
+impl Default for Tolerances {
+    fn default() -> Self {
+        // Enough to absorb f32 rounding noise such as 1.056 vs 1.0559999.
+        let noise = Tolerance {
+            abs: 0.0005,
+            rel: 1e-5,
+            tick: None,
+        };
+        Tolerances {
+            recharge_time: noise,
+            cast_time: noise,
+            accuracy: noise,
+            scale: noise,
+        }
+    }
+}
+
+impl Tolerances {
+    pub fn load(path: &Path) -> Result<Self> {
+        export::read_json(path)
+    }
+}
@@ -0,0 +1,152 @@
+use std::{
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use crate::mids;
+
+/// How to change Database::version when saving.
+pub enum Policy {
+    /// Increment the last dot-separated component.
+    Bump,
+    /// Use the current UTC date, as year.month.day.
+    Date,
+    /// Leave version and datetime alone.
+    Keep,
+    /// Use exactly this version.
+    Set(String),
+}
+
+impl FromStr for Policy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "bump" => Policy::Bump,
+            "date" => Policy::Date,
+            "keep" => Policy::Keep,
+            v if v.split('.').all(|c| !c.is_empty() && c.bytes().all(|b| b.is_ascii_digit())) => Policy::Set(v.to_string()),
+            v => bail!("invalid version {:?}: expected dot-separated numbers", v),
+        })
+    }
+}
+
+/// .NET DateTime ticks (100ns since 0001-01-01) at the Unix epoch.
+const UNIX_EPOCH_TICKS: i64 = 621_355_968_000_000_000;
+/// DateTime.ToBinary() flag for DateTimeKind.Utc.
+const KIND_UTC: i64 = 1 << 62;
+
+/// Converts days since the Unix epoch to a (year, month, day) civil date.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    (yoe + era * 400 + i64::from(m <= 2), m, d)
+}
+
+fn bump(version: &str) -> Result<String> {
+    let (head, build) = version
+        .rsplit_once('.')
+        .ok_or_else(|| anyhow!("can't bump version {:?}: no '.'", version))?;
+    let build: u64 = build
+        .parse()
+        .with_context(|| format!("can't bump version {:?}: last component isn't a number", version))?;
+    Ok(format!("{}.{}", head, build + 1))
+}
+
+/// Converts seconds since the Unix epoch to a UTC DateTime, as written by DateTime.ToBinary().
+fn datetime_binary(secs: i64) -> i64 {
+    (UNIX_EPOCH_TICKS + secs * 10_000_000) | KIND_UTC
+}
+
+/// Applies the policy to the version, and stamps the database with `now`.
+pub fn apply(mdb: &mut mids::Database, policy: &Policy, now: SystemTime) -> Result<()> {
+    let secs = now.duration_since(UNIX_EPOCH)?.as_secs() as i64;
+    mdb.version = match policy {
+        Policy::Keep => return Ok(()),
+        Policy::Bump => bump(&mdb.version)?,
+        Policy::Date => {
+            let (y, m, d) = civil_from_days(secs.div_euclid(86400));
+            format!("{}.{}.{}", y, m, d)
+        }
+        Policy::Set(v) => v.clone(),
+    };
+    mdb.datetime = datetime_binary(secs);
+    Ok(())
+}
+
+/// The issue and page of a CoD revision.
+pub struct Revision {
+    pub issue: u32,
+    pub page: u32,
+    pub name: String,
+}
+
+/// Parses a CoD revision of the form "i27p7" (optionally followed by more text, which is ignored
+/// for the numbers).
+pub fn parse_revision(revision: &str) -> Result<Revision> {
+    let lower = revision.to_ascii_lowercase();
+    let parse = || -> Option<(u32, u32)> {
+        let rest = lower.strip_prefix('i')?;
+        let (issue, rest) = rest.split_once('p')?;
+        let page: String = rest.chars().take_while(char::is_ascii_digit).collect();
+        Some((issue.parse().ok()?, page.parse().ok()?))
+    };
+    let (issue, page) = parse().ok_or_else(|| anyhow!("can't find issue and page in CoD revision {:?}", revision))?;
+    Ok(Revision {
+        issue,
+        page,
+        name: revision.to_string(),
+    })
+}
+
+/// Sets issue, page_volume and page_volume_name from a CoD revision.
+pub fn apply_revision(mdb: &mut mids::Database, revision: &Revision) {
+    mdb.issue = revision.issue;
+    mdb.page_volume = revision.page;
+    mdb.page_volume_name.clone_from(&revision.name);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn civil_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        assert_eq!(civil_from_days(19723), (2024, 1, 1));
+    }
+
+    #[test]
+    fn datetime_ticks() {
+        // DateTime(1970, 1, 1, 0, 0, 0, DateTimeKind.Utc).ToBinary()
+        assert_eq!(datetime_binary(0), 5_233_041_986_427_387_904);
+        // DateTime(2024, 1, 1, 0, 0, 0, DateTimeKind.Utc).ToBinary()
+        assert_eq!(datetime_binary(19723 * 86400), 5_250_082_658_427_387_904);
+    }
+
+    #[test]
+    fn policies() {
+        assert!(matches!("1.2.3".parse(), Ok(Policy::Set(v)) if v == "1.2.3"));
+        assert!(matches!("bump".parse(), Ok(Policy::Bump)));
+        for bad in ["", "1..2", "1.2.", "1.x", "v1.2"] {
+            assert!(bad.parse::<Policy>().is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn revisions() {
+        let r = parse_revision("I27P7 (2024-05-01)").unwrap();
+        assert_eq!((r.issue, r.page), (27, 7));
+        assert!(parse_revision("live").is_err());
+    }
+}